@@ -0,0 +1,114 @@
+use std::sync::Arc;
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use leviathan::engine::{
+    config::AccountConfig,
+    domain::{TransactionEvent, TransactionType},
+    ledger::{Account, InMemoryLedger, Ledger},
+};
+use rust_decimal::Decimal;
+
+fn event(
+    transaction_type: TransactionType,
+    tx_id: u32,
+    amount: Option<Decimal>,
+) -> TransactionEvent {
+    let counterparty = match transaction_type {
+        TransactionType::Transfer => Some(2),
+        _ => None,
+    };
+    TransactionEvent {
+        client_id: 1,
+        tx_id,
+        transaction_type,
+        amount,
+        counterparty,
+    }
+}
+
+/// An account with enough funds for every withdrawal and transfer.
+fn funded(config: &AccountConfig) -> Account {
+    Account::new(
+        1,
+        event(
+            TransactionType::Deposit,
+            1,
+            Some(Decimal::new(1_000_000_000_000, 0)),
+        ),
+        config,
+    )
+}
+
+fn apply_tx(c: &mut Criterion) {
+    let config = AccountConfig::default();
+    let mut group = c.benchmark_group("apply_tx");
+
+    // Every transaction is applied to a fresh copy of an account, so that the account does
+    // not grow with the iterations. Disputes and their settlements need a deposit to refer to.
+    let undisputed = funded(&config);
+    let mut disputed = undisputed.clone();
+    disputed
+        .apply_tx(1, event(TransactionType::Dispute, 1, None), &config)
+        .unwrap();
+    for (transaction_type, account, tx_id, amount) in [
+        (
+            TransactionType::Deposit,
+            &undisputed,
+            2,
+            Some(Decimal::new(1, 2)),
+        ),
+        (
+            TransactionType::Withdrawal,
+            &undisputed,
+            2,
+            Some(Decimal::new(1, 2)),
+        ),
+        (TransactionType::Dispute, &undisputed, 1, None),
+        (TransactionType::Resolve, &disputed, 1, None),
+        (TransactionType::Chargeback, &disputed, 1, None),
+    ] {
+        group.bench_function(format!("{transaction_type:?}").to_lowercase(), |b| {
+            b.iter_batched(
+                || account.clone(),
+                |mut account| {
+                    let event = event(transaction_type.clone(), tx_id, amount);
+                    black_box(account.apply_tx(tx_id, event, &config)).unwrap();
+                    account
+                },
+                BatchSize::SmallInput,
+            )
+        });
+    }
+
+    // A transfer changes two accounts, so it goes through a ledger like in a run.
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let receiver = Account::new(
+        2,
+        TransactionEvent {
+            client_id: 2,
+            ..event(TransactionType::Deposit, 1, Some(Decimal::new(1, 0)))
+        },
+        &config,
+    );
+    group.bench_function("transfer", |b| {
+        b.iter_batched(
+            || {
+                InMemoryLedger::with_aggregates(
+                    vec![(1, undisputed.clone()), (2, receiver.clone())],
+                    config.clone(),
+                )
+            },
+            |ledger| {
+                let event = event(TransactionType::Transfer, 2, Some(Decimal::new(1, 2)));
+                black_box(runtime.block_on(Arc::clone(&ledger).process_transaction(1, 2, event)))
+                    .unwrap();
+                ledger
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, apply_tx);
+criterion_main!(benches);
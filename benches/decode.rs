@@ -0,0 +1,41 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use futures::StreamExt;
+use leviathan::{
+    generator::{Generator, GeneratorConfig},
+    listener::{csv_reader, AsUpdateStream},
+};
+
+fn decode(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let mut group = c.benchmark_group("decode");
+    for rows in [10_000, 100_000] {
+        let mut csv = Vec::new();
+        let generator = Generator::new(GeneratorConfig {
+            rows,
+            ..GeneratorConfig::default()
+        });
+        runtime.block_on(generator.write(&mut csv)).unwrap();
+        // The listener needs a `'static` reader, so the input is leaked once per size
+        // rather than copied in every iteration.
+        let csv: &'static [u8] = Box::leak(csv.into_boxed_slice());
+
+        group.throughput(Throughput::Elements(rows.into()));
+        group.bench_with_input(BenchmarkId::new("csv", rows), &csv, |b, &csv| {
+            b.iter(|| {
+                runtime.block_on(async {
+                    let mut listener = csv_reader("bench.csv", std::io::Cursor::new(csv));
+                    let events = listener
+                        .as_stream()
+                        .filter(|event| futures::future::ready(event.is_ok()))
+                        .count()
+                        .await;
+                    assert_eq!(events, rows as usize);
+                })
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, decode);
+criterion_main!(benches);
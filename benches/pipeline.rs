@@ -0,0 +1,71 @@
+use std::{path::PathBuf, sync::Arc, time::Duration};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use leviathan::{
+    engine::{
+        domain::AccountSnapshot,
+        ledger::{Account, InMemoryLedger, Rejection},
+    },
+    generator::{Generator, GeneratorConfig},
+    listener::polling,
+    Pipeline,
+};
+use tokio_util::sync::CancellationToken;
+
+/// Generates the input file of `rows` rows once, and reuses it in later runs.
+fn input(runtime: &tokio::runtime::Runtime, rows: u32) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("leviathan-bench-{rows}.csv"));
+    if !path.exists() {
+        let generator = Generator::new(GeneratorConfig {
+            clients: std::num::NonZeroU16::new(10_000).unwrap(),
+            rows,
+            ..GeneratorConfig::default()
+        });
+        runtime
+            .block_on(async { generator.write(tokio::fs::File::create(&path).await?).await })
+            .unwrap();
+    }
+    path
+}
+
+/// The peak resident set size of the process in KiB, on Linux.
+fn peak_memory() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmHWM:"))?;
+    line.split_whitespace().nth(1)?.parse().ok()
+}
+
+/// Lets the next [`peak_memory`] start from the current resident set size.
+fn reset_peak_memory() {
+    let _ = std::fs::write("/proc/self/clear_refs", "5");
+}
+
+fn pipeline(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let mut group = c.benchmark_group("pipeline");
+    group.sample_size(10);
+    for rows in [1_000_000, 10_000_000] {
+        let path = input(&runtime, rows);
+        group.throughput(Throughput::Elements(rows.into()));
+        group.measurement_time(Duration::from_secs(u64::from(rows / 100_000)));
+        reset_peak_memory();
+        group.bench_with_input(BenchmarkId::from_parameter(rows), &path, |b, path| {
+            b.iter(|| {
+                runtime.block_on(async {
+                    let ledger = InMemoryLedger::<Account>::new();
+                    Pipeline::new(ledger, |_: Vec<AccountSnapshot>| async {})
+                        .rejection_handler(Arc::new(|_: Rejection<Account>| async {}))
+                        .run(polling(path).await.unwrap(), CancellationToken::new())
+                        .await;
+                })
+            })
+        });
+        if let Some(peak) = peak_memory() {
+            println!("pipeline/{rows}: peak memory {} MiB", peak / 1024);
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, pipeline);
+criterion_main!(benches);
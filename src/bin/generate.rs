@@ -0,0 +1,86 @@
+use std::{num::NonZeroU16, path::PathBuf, process};
+
+use clap::Parser;
+use leviathan::generator::{Generator, GeneratorConfig, Mix};
+use tokio::{fs::File, io};
+
+/// The output file could not be created.
+const EX_CANTCREAT: i32 = 73;
+/// Writing the output failed.
+const EX_IOERR: i32 = 74;
+
+/// Generate a CSV file of random transactions for the payments engine.
+#[derive(Parser)]
+#[clap(version)]
+struct Cli {
+    /// Number of clients, numbered from 1.
+    #[clap(long, default_value = "100")]
+    clients: NonZeroU16,
+
+    /// Number of rows, not counting the header.
+    #[clap(long, default_value = "1000")]
+    rows: u32,
+
+    /// Relative weights of deposits, withdrawals and transfers.
+    #[clap(long, default_value = "deposit=6,withdrawal=3,transfer=1")]
+    mix: Mix,
+
+    /// Chance of a row disputing a recent deposit or transfer, and of a row settling an
+    /// open dispute.
+    #[clap(long, default_value = "0.02", parse(try_from_str = rate))]
+    dispute_rate: f64,
+
+    /// Chance of a dispute being settled with a chargeback rather than a resolve.
+    #[clap(long, default_value = "0.25", parse(try_from_str = rate))]
+    chargeback_rate: f64,
+
+    /// Chance of a row that cannot be decoded.
+    #[clap(long, default_value = "0", parse(try_from_str = rate))]
+    malformed_rate: f64,
+
+    /// Seed of the random number generator. The same seed and options always generate
+    /// the same file.
+    #[clap(long, default_value = "0")]
+    seed: u64,
+
+    /// Write the transactions to this file instead of stdout.
+    #[clap(long, short)]
+    output: Option<PathBuf>,
+}
+
+fn rate(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(rate) if (0.0..=1.0).contains(&rate) => Ok(rate),
+        Ok(_) => Err(format!("`{s}` is not between 0 and 1")),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+    let generator = Generator::new(GeneratorConfig {
+        clients: cli.clients,
+        rows: cli.rows,
+        mix: cli.mix,
+        dispute_rate: cli.dispute_rate,
+        chargeback_rate: cli.chargeback_rate,
+        malformed_rate: cli.malformed_rate,
+        seed: cli.seed,
+    });
+
+    let result = match &cli.output {
+        Some(path) => match File::create(path).await {
+            Ok(file) => generator.write(file).await,
+            Err(err) => {
+                eprintln!("Failed to create `{}`: {err}", path.display());
+                process::exit(EX_CANTCREAT);
+            }
+        },
+        None => generator.write(io::stdout()).await,
+    };
+    if let Err(err) = result {
+        eprintln!("Failed to write the transactions: {err}");
+        process::exit(EX_IOERR);
+    }
+}
@@ -0,0 +1,141 @@
+use std::{
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use tokio::{fs, io};
+
+use crate::{
+    engine::ledger::{Account, Aggregate, Aggregates},
+    TransactionObserver,
+};
+
+/// The accounts after the first `transactions` entries of a [`Journal`](crate::journal::Journal),
+/// so that a replay can resume from there instead of from the start.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// How many journal entries the accounts include.
+    pub transactions: u64,
+    pub accounts: Aggregates<Account>,
+}
+
+impl Checkpoint {
+    /// Reads the JSON checkpoint at `path`. The books of the accounts start over from their
+    /// balances.
+    pub async fn load<P>(path: P) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let mut checkpoint: Self = serde_json::from_slice(&fs::read(path).await?)?;
+        for (id, account) in &mut checkpoint.accounts {
+            account.open_books(*id);
+        }
+        Ok(checkpoint)
+    }
+
+    /// Writes the checkpoint as JSON to `path`, replacing it only once it is complete.
+    pub async fn save<P>(&self, path: P) -> io::Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let partial = path.with_extension("partial");
+        fs::write(&partial, serde_json::to_vec(self)?).await?;
+        fs::rename(partial, path).await
+    }
+}
+
+/// Counts the transactions of a run, so that its accounts can be saved as a [`Checkpoint`].
+pub struct Checkpointer {
+    transactions: AtomicU64,
+}
+
+impl Checkpointer {
+    /// Starts counting after the first `offset` transactions, e.g. those of the checkpoint a
+    /// replay resumed from.
+    pub fn new(offset: u64) -> Arc<Self> {
+        Arc::new(Self {
+            transactions: AtomicU64::new(offset),
+        })
+    }
+
+    /// A checkpoint of `accounts` after the transactions counted so far.
+    pub fn checkpoint(&self, accounts: Aggregates<Account>) -> Checkpoint {
+        Checkpoint {
+            transactions: self.transactions.load(Ordering::SeqCst),
+            accounts,
+        }
+    }
+}
+
+impl<A: Aggregate> TransactionObserver<A> for Checkpointer {
+    fn observe<'a>(&'a self, _event: &'a A::EventData, _accepted: bool) -> BoxFuture<'a, ()> {
+        self.transactions.fetch_add(1, Ordering::SeqCst);
+        Box::pin(async {})
+    }
+
+    fn finish<'a>(&'a self, _snapshots: &'a [A::Snapshot]) -> BoxFuture<'a, ()> {
+        Box::pin(async {})
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rust_decimal_macros::dec;
+
+    use super::*;
+    use crate::engine::{
+        config::AccountConfig,
+        domain::{TransactionEvent, TransactionType},
+        ledger::{InMemoryLedger, Ledger},
+    };
+
+    #[tokio::test]
+    async fn test_checkpoint_round_trip() {
+        let ledger = InMemoryLedger::<Account>::new();
+        let checkpointer = Checkpointer::new(2);
+        for (tx_id, transaction_type) in
+            [(1, TransactionType::Deposit), (1, TransactionType::Dispute)]
+        {
+            let event = TransactionEvent {
+                client_id: 1,
+                tx_id,
+                transaction_type,
+                amount: Some(dec!(3)),
+                counterparty: None,
+            };
+            TransactionObserver::<Account>::observe(&*checkpointer, &event, true).await;
+            Arc::clone(&ledger)
+                .process_transaction(1, tx_id, event)
+                .await
+                .unwrap();
+        }
+        let path = std::env::temp_dir().join(format!("checkpoint-{}.json", std::process::id()));
+        let accounts = Arc::clone(&ledger).aggregates().await.unwrap();
+        checkpointer.checkpoint(accounts).save(&path).await.unwrap();
+
+        let checkpoint = Checkpoint::load(&path).await.unwrap();
+        fs::remove_file(&path).await.unwrap();
+        assert_eq!(checkpoint.transactions, 4);
+        let restored =
+            InMemoryLedger::with_aggregates(checkpoint.accounts, AccountConfig::default());
+        // The dispute is restored, so the deposit can still be resolved.
+        let resolve = TransactionEvent {
+            client_id: 1,
+            tx_id: 1,
+            transaction_type: TransactionType::Resolve,
+            amount: None,
+            counterparty: None,
+        };
+        Arc::clone(&restored)
+            .process_transaction(1, 1, resolve)
+            .await
+            .unwrap();
+        assert_eq!(restored.snapshot(1).await.unwrap().available, dec!(3));
+    }
+}
@@ -0,0 +1,206 @@
+use std::{collections::BTreeMap, fmt};
+
+use rust_decimal::Decimal;
+use serde::Serialize;
+use tokio::io::{self, AsyncWrite, AsyncWriteExt};
+
+use crate::engine::error::LedgerError;
+
+/// An account of the double-entry books, as opposed to a client
+/// [`Account`](crate::engine::ledger::Account).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum LedgerAccount {
+    /// Funds the client can use.
+    Available(u16),
+    /// Funds of the client held for disputes.
+    Held(u16),
+    /// Funds entering and leaving through deposits and withdrawals, and interest and fees
+    /// without a house account.
+    Funding,
+    /// Funds taken back by chargebacks.
+    ChargebackLoss,
+}
+
+impl fmt::Display for LedgerAccount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Available(client) => write!(f, "client {client} available"),
+            Self::Held(client) => write!(f, "client {client} held"),
+            Self::Funding => f.write_str("funding"),
+            Self::ChargebackLoss => f.write_str("chargeback loss"),
+        }
+    }
+}
+
+/// One side of a transaction in the books. Debits are positive and credits negative, so
+/// the client accounts, which the ledger owes, have credit balances.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Posting {
+    pub account: LedgerAccount,
+    pub amount: Decimal,
+}
+
+impl Posting {
+    pub fn debit(account: LedgerAccount, amount: Decimal) -> Self {
+        Self { account, amount }
+    }
+
+    pub fn credit(account: LedgerAccount, amount: Decimal) -> Self {
+        Self {
+            account,
+            amount: -amount,
+        }
+    }
+}
+
+/// The balances of the ledger accounts the postings of one client account went to.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Book {
+    balances: BTreeMap<LedgerAccount, Decimal>,
+}
+
+impl Book {
+    /// Fails with [`LedgerError::UnbalancedPostings`] unless `postings` sum to zero.
+    pub fn check(tx_id: u32, postings: &[Posting]) -> Result<(), LedgerError> {
+        let difference = postings
+            .iter()
+            .map(|posting| posting.amount)
+            .sum::<Decimal>();
+        if difference.is_zero() {
+            Ok(())
+        } else {
+            Err(LedgerError::UnbalancedPostings { tx_id, difference })
+        }
+    }
+
+    pub fn post(&mut self, postings: Vec<Posting>) {
+        for posting in postings {
+            *self.balances.entry(posting.account).or_default() += posting.amount;
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Row {
+    account: String,
+    debit: Decimal,
+    credit: Decimal,
+}
+
+/// The balance of every ledger account over the books of all client accounts.
+///
+/// The debits and credits of a ledger that neither creates nor destroys money are equal.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct TrialBalance {
+    pub balances: BTreeMap<LedgerAccount, Decimal>,
+}
+
+impl TrialBalance {
+    /// The balance of `account`, zero if nothing was posted to it.
+    pub fn balance(&self, account: &LedgerAccount) -> Decimal {
+        self.balances.get(account).copied().unwrap_or_default()
+    }
+
+    pub fn debits(&self) -> Decimal {
+        self.balances.values().map(|balance| debit(*balance)).sum()
+    }
+
+    pub fn credits(&self) -> Decimal {
+        self.balances.values().map(|balance| credit(*balance)).sum()
+    }
+
+    pub fn is_balanced(&self) -> bool {
+        self.debits() == self.credits()
+    }
+
+    /// Writes a CSV row per ledger account, and a final row with the totals.
+    pub async fn write<W>(&self, mut writer: W) -> io::Result<()>
+    where
+        W: AsyncWrite + Unpin + Send,
+    {
+        let mut wri = csv_async::AsyncWriterBuilder::new()
+            .has_headers(true)
+            .create_serializer(&mut writer);
+        for (account, balance) in &self.balances {
+            let row = Row {
+                account: account.to_string(),
+                debit: debit(*balance),
+                credit: credit(*balance),
+            };
+            wri.serialize(row).await.map_err(io::Error::from)?;
+        }
+        let total = Row {
+            account: "total".to_owned(),
+            debit: self.debits(),
+            credit: self.credits(),
+        };
+        wri.serialize(total).await.map_err(io::Error::from)?;
+        wri.flush().await?;
+        drop(wri);
+        writer.flush().await
+    }
+}
+
+fn debit(balance: Decimal) -> Decimal {
+    if balance > Decimal::default() {
+        balance
+    } else {
+        Decimal::default()
+    }
+}
+
+fn credit(balance: Decimal) -> Decimal {
+    debit(-balance)
+}
+
+impl<'a> FromIterator<&'a Book> for TrialBalance {
+    fn from_iter<T: IntoIterator<Item = &'a Book>>(iter: T) -> Self {
+        let mut balances = BTreeMap::new();
+        for book in iter {
+            for (account, balance) in &book.balances {
+                *balances.entry(*account).or_default() += *balance;
+            }
+        }
+        Self { balances }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rust_decimal_macros::dec;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_trial_balance() {
+        let mut first = Book::default();
+        let deposit = vec![
+            Posting::debit(LedgerAccount::Funding, dec!(10)),
+            Posting::credit(LedgerAccount::Available(1), dec!(10)),
+        ];
+        Book::check(1, &deposit).unwrap();
+        first.post(deposit);
+        let mut second = Book::default();
+        second.post(vec![
+            Posting::debit(LedgerAccount::Available(1), dec!(4)),
+            Posting::credit(LedgerAccount::Available(2), dec!(4)),
+        ]);
+        assert!(matches!(
+            Book::check(3, &[Posting::debit(LedgerAccount::Funding, dec!(1))]),
+            Err(LedgerError::UnbalancedPostings { tx_id: 3, .. })
+        ));
+
+        let trial_balance = [&first, &second].into_iter().collect::<TrialBalance>();
+        assert_eq!(
+            trial_balance.balances[&LedgerAccount::Available(1)],
+            dec!(-6)
+        );
+        assert!(trial_balance.is_balanced());
+        let mut csv = Vec::new();
+        trial_balance.write(&mut csv).await.unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "account,debit,credit\nclient 1 available,0,6\nclient 2 available,0,4\nfunding,10,0\ntotal,10,10\n"
+        );
+    }
+}
@@ -0,0 +1,30 @@
+use crate::engine::{fees::FeeSchedule, limits::Limits, rules::RuleSet};
+
+/// Policies that change how an [`Account`](crate::engine::ledger::Account) applies transactions.
+///
+/// The default is the strictest behaviour.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct AccountConfig {
+    /// Let a dispute hold funds even when this makes the available balance negative.
+    pub allow_negative_available: bool,
+    /// Accept deposits and withdrawals whose transaction ID is not greater than the previous one.
+    pub allow_out_of_order: bool,
+    /// Risk rules every transaction is checked against before it is applied.
+    pub rules: RuleSet,
+    /// Withdrawal limits and overdraft allowances of individual clients.
+    pub limits: Limits,
+    /// Fees charged alongside deposits, withdrawals and transfers.
+    pub fees: FeeSchedule,
+    /// The client that collects fees and pays interest, if any.
+    pub house: Option<u16>,
+    /// Keep double-entry books of every transaction, see
+    /// [`TrialBalance`](crate::engine::bookkeeping::TrialBalance).
+    pub double_entry: bool,
+}
+
+impl AccountConfig {
+    /// How many of its latest transactions the account of `client_id` has to remember.
+    pub fn history(&self, client_id: u16) -> usize {
+        self.rules.window().max(self.limits.window(client_id))
+    }
+}
@@ -2,7 +2,7 @@ use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 
 /// Transaction type enum
-#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 #[serde(rename_all = "lowercase")]
 pub enum TransactionType {
     Deposit,
@@ -10,6 +10,12 @@ pub enum TransactionType {
     Dispute,
     Resolve,
     Chargeback,
+    /// Moves `amount` from `client` to `counterparty`.
+    Transfer,
+    /// Charges `client` a fee of `amount`.
+    Fee,
+    /// Pays `client` interest of `amount`.
+    Interest,
 }
 
 #[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
@@ -23,12 +29,15 @@ pub struct TransactionEvent {
     /// Transaction type ( deposit, withdrawal, dispute, etc.)
     #[serde(rename = "type")]
     pub transaction_type: TransactionType,
-    /// Transaction amount, if withdrawal or deposit type
+    /// Transaction amount, if withdrawal, deposit, transfer, fee or interest type
     pub amount: Option<Decimal>,
+    /// Receiving client, if transfer type
+    #[serde(default)]
+    pub counterparty: Option<u16>,
 }
 
 /// Balance for the account
-#[derive(Debug, Default, PartialEq, Clone)]
+#[derive(Debug, Default, PartialEq, Clone, Deserialize, Serialize)]
 pub struct Balance {
     /// The total funds that are available. This should be equal to the total - held amounts
     pub available: Decimal,
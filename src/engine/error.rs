@@ -16,4 +16,85 @@ pub enum LedgerError {
     SuspiciousTransaction(u32),
     #[error("Associated Transaction `{0}` is missing an amount when one is expected")]
     MissingAmount(u32),
+    #[error("Transfer `{0}` is missing a counterparty, or names the sending client")]
+    InvalidCounterparty(u32),
+    #[error(
+        "Transaction `{tx_id}` moves funds to or from client `{client}`, which does not exist"
+    )]
+    UnknownCounterparty { tx_id: u32, client: u16 },
+    #[error("Transfer `{0}` can only be disputed by the receiving client")]
+    OutgoingTransferDispute(u32),
+    #[error("Transaction: `{tx_id}` was rejected by rule `{rule}`")]
+    RuleViolation { rule: String, tx_id: u32 },
+    #[error("Transaction: `{tx_id}` froze the account by rule `{rule}`")]
+    AccountFrozen { rule: String, tx_id: u32 },
+    #[error("The withdrawal exceeds the limit of the account. Limit {limit:?}, Transaction amount {amount:?}")]
+    WithdrawalLimitExceeded { limit: Decimal, amount: Decimal },
+    #[error("The withdrawals within the last {window} transactions exceed the cap of the account. Cap {cap:?}, Withdrawn {withdrawn:?}")]
+    WithdrawalCapExceeded {
+        cap: Decimal,
+        window: usize,
+        withdrawn: Decimal,
+    },
+    #[error("The withdrawal exceeds the overdraft of the account. Available {available:?}, Overdraft {overdraft:?}, Transaction amount {amount:?}")]
+    OverdraftExceeded {
+        available: Decimal,
+        overdraft: Decimal,
+        amount: Decimal,
+    },
+    #[error(
+        "The postings of transaction `{tx_id}` do not balance, they are off by {difference:?}"
+    )]
+    UnbalancedPostings { tx_id: u32, difference: Decimal },
+    #[error("The fee of deposit `{tx_id}` exceeds its amount. Fee {fee:?}, Transaction amount {amount:?}")]
+    FeeExceedsAmount {
+        tx_id: u32,
+        fee: Decimal,
+        amount: Decimal,
+    },
+    #[error("Transaction `{0}` posts a fee or interest between the house and itself")]
+    HousePosting(u32),
+}
+
+impl LedgerError {
+    /// Name of the variant, used to group errors in reports.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::LockedAccount(_) => "LockedAccount",
+            Self::TransactionNotFound(_) => "TransactionNotFound",
+            Self::InsufficientFunds { .. } => "InsufficientFunds",
+            Self::DisputedTransaction(_) => "DisputedTransaction",
+            Self::SuspiciousTransaction(_) => "SuspiciousTransaction",
+            Self::MissingAmount(_) => "MissingAmount",
+            Self::InvalidCounterparty(_) => "InvalidCounterparty",
+            Self::UnknownCounterparty { .. } => "UnknownCounterparty",
+            Self::OutgoingTransferDispute(_) => "OutgoingTransferDispute",
+            Self::RuleViolation { .. } => "RuleViolation",
+            Self::AccountFrozen { .. } => "AccountFrozen",
+            Self::WithdrawalLimitExceeded { .. } => "WithdrawalLimitExceeded",
+            Self::WithdrawalCapExceeded { .. } => "WithdrawalCapExceeded",
+            Self::OverdraftExceeded { .. } => "OverdraftExceeded",
+            Self::UnbalancedPostings { .. } => "UnbalancedPostings",
+            Self::FeeExceedsAmount { .. } => "FeeExceedsAmount",
+            Self::HousePosting(_) => "HousePosting",
+        }
+    }
+}
+
+/// Error of a [`Ledger`](crate::engine::ledger::Ledger) operation on the aggregate `ID`,
+/// whose transactions fail with `E`.
+#[derive(Debug, Error)]
+pub enum StoreError<ID, E> {
+    #[error("Aggregate `{0}` does not exist")]
+    NotFound(ID),
+    #[error("Storage failure: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Storage failure: {0}")]
+    Storage(#[source] Box<dyn std::error::Error + Send + Sync>),
+    #[error("Transaction rejected: {0}")]
+    Rejected(E),
+    #[error("The ledger is closed")]
+    Closed,
+    #[error("The ledger does not support listing {0}")]
+    Unsupported(&'static str),
 }
@@ -0,0 +1,137 @@
+use std::{collections::HashMap, io, path::Path};
+
+use rust_decimal::Decimal;
+use serde::Deserialize;
+
+use crate::engine::domain::{TransactionEvent, TransactionType};
+
+/// The fee charged for transactions of one type.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct Fee {
+    #[serde(rename = "type")]
+    pub transaction_type: TransactionType,
+    /// Charged for every transaction.
+    #[serde(default)]
+    pub flat: Option<Decimal>,
+    /// Percentage of the transaction amount, charged on top of `flat`.
+    #[serde(default)]
+    pub percent: Option<Decimal>,
+}
+
+/// The fees charged alongside transactions, by transaction type.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct FeeSchedule {
+    fees: HashMap<TransactionType, Fee>,
+}
+
+impl FeeSchedule {
+    /// Reads the schedule from a CSV file with a `type` column and `flat` and `percent`
+    /// columns, either of which may be empty. Negative fees and fees of types other than
+    /// deposits, withdrawals and transfers are refused.
+    pub async fn load(path: &Path) -> io::Result<Self> {
+        let file = tokio::fs::File::open(path).await?;
+        let mut reader = csv_async::AsyncReaderBuilder::new()
+            .flexible(true)
+            .trim(csv_async::Trim::All)
+            .create_deserializer(file);
+        let invalid = |err: csv_async::Error| io::Error::new(io::ErrorKind::InvalidData, err);
+        let headers = reader.headers().await.map_err(invalid)?.clone();
+        let mut record = csv_async::StringRecord::new();
+        let mut fees = Vec::new();
+        while reader.read_record(&mut record).await.map_err(invalid)? {
+            let fee = record.deserialize::<Fee>(Some(&headers)).map_err(invalid)?;
+            let chargeable = matches!(
+                fee.transaction_type,
+                TransactionType::Deposit | TransactionType::Withdrawal | TransactionType::Transfer
+            );
+            let negative = fee.flat.unwrap_or_default() < Decimal::default()
+                || fee.percent.unwrap_or_default() < Decimal::default();
+            if !chargeable || negative {
+                let line = record.position().map_or(0, |position| position.line());
+                let reason = if chargeable {
+                    "is negative"
+                } else {
+                    "cannot be charged, only deposits, withdrawals and transfers have fees"
+                };
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "Line {line}: the fee of `{:?}` {reason}",
+                        fee.transaction_type
+                    ),
+                ));
+            }
+            fees.push(fee);
+        }
+        Ok(fees.into_iter().collect())
+    }
+
+    /// The fee charged for `event`, zero if its type has none.
+    pub fn fee(&self, event: &TransactionEvent) -> Decimal {
+        let fee = match self.fees.get(&event.transaction_type) {
+            Some(fee) => fee,
+            None => return Decimal::default(),
+        };
+        let percent = match (fee.percent, event.amount) {
+            (Some(percent), Some(amount)) => amount * percent / Decimal::from(100),
+            _ => Decimal::default(),
+        };
+        fee.flat.unwrap_or_default() + percent
+    }
+}
+
+impl FromIterator<Fee> for FeeSchedule {
+    fn from_iter<T: IntoIterator<Item = Fee>>(iter: T) -> Self {
+        Self {
+            fees: iter
+                .into_iter()
+                .map(|fee| (fee.transaction_type.clone(), fee))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rust_decimal_macros::dec;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_fee_schedule() {
+        let path = std::env::temp_dir().join(format!("leviathan-fees-{}.csv", std::process::id()));
+        tokio::fs::write(&path, "type,flat,percent\nwithdrawal,0.5,1\ntransfer,,2\n")
+            .await
+            .unwrap();
+        let schedule = FeeSchedule::load(&path).await;
+        tokio::fs::remove_file(&path).await.unwrap();
+        let schedule = schedule.unwrap();
+
+        let event = |transaction_type| TransactionEvent {
+            client_id: 1,
+            tx_id: 1,
+            transaction_type,
+            amount: Some(dec!(50)),
+            counterparty: None,
+        };
+        assert_eq!(schedule.fee(&event(TransactionType::Withdrawal)), dec!(1));
+        assert_eq!(schedule.fee(&event(TransactionType::Transfer)), dec!(1));
+        assert_eq!(schedule.fee(&event(TransactionType::Deposit)), dec!(0));
+
+        tokio::fs::write(&path, "type,flat,percent\nwithdrawal,,-1\n")
+            .await
+            .unwrap();
+        let negative = FeeSchedule::load(&path).await;
+        tokio::fs::remove_file(&path).await.unwrap();
+        assert_eq!(negative.unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        tokio::fs::write(&path, "type,flat,percent\nwithdrawal,1,\ndispute,1,\n")
+            .await
+            .unwrap();
+        let dispute = FeeSchedule::load(&path).await;
+        tokio::fs::remove_file(&path).await.unwrap();
+        let error = dispute.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().starts_with("Line 3:"), "{error}");
+    }
+}
@@ -0,0 +1,125 @@
+use std::sync::Arc;
+
+use tokio::sync::{mpsc, oneshot};
+
+use crate::engine::{
+    error::StoreError,
+    ledger::{Aggregate, Ledger, LedgerResult, Route},
+};
+
+type Submission<A> = (
+    <A as Aggregate>::EventData,
+    oneshot::Sender<LedgerResult<A, <A as Aggregate>::Snapshot>>,
+);
+
+/// A cloneable handle to submit transactions to a ledger and wait for their result.
+///
+/// Transactions are applied one at a time in the order they are submitted, over all clones
+/// of the handle. The engine stops once every handle is dropped.
+pub struct EngineHandle<A: Aggregate> {
+    tx: mpsc::UnboundedSender<Submission<A>>,
+}
+
+impl<A: Aggregate> Clone for EngineHandle<A> {
+    fn clone(&self) -> Self {
+        Self {
+            tx: self.tx.clone(),
+        }
+    }
+}
+
+impl<A> EngineHandle<A>
+where
+    A: Aggregate + Send + Sync + 'static,
+    A::EventData: Route<A::ID, A::TxID> + Clone + 'static,
+    A::Snapshot: 'static,
+    A::Error: Send + 'static,
+{
+    /// Spawns an engine that applies the submitted transactions to `ledger`.
+    pub fn spawn<L>(ledger: Arc<L>) -> Self
+    where
+        L: Ledger<A> + Send + Sync + 'static,
+    {
+        let (tx, mut rx) = mpsc::unbounded_channel::<Submission<A>>();
+        tokio::spawn(async move {
+            while let Some((event, reply)) = rx.recv().await {
+                let (id, tx_id) = event.route();
+                let result = match Arc::clone(&ledger)
+                    .process_transaction(id, tx_id, event)
+                    .await
+                {
+                    Ok(applied) => Arc::clone(&ledger).snapshot(applied.id).await,
+                    Err(error) => Err(error),
+                };
+                // The submitter may have stopped waiting, which is fine.
+                let _ = reply.send(result);
+            }
+        });
+        Self { tx }
+    }
+
+    /// Applies `event` and returns the snapshot of the aggregate it applied to.
+    ///
+    /// Fails with [`StoreError::Rejected`] if the aggregate refuses the transaction, and
+    /// with [`StoreError::Closed`] if the engine stopped.
+    pub async fn submit(&self, event: A::EventData) -> LedgerResult<A, A::Snapshot> {
+        let (reply, result) = oneshot::channel();
+        self.tx
+            .send((event, reply))
+            .map_err(|_| StoreError::Closed)?;
+        result.await.map_err(|_| StoreError::Closed)?
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::future::join_all;
+    use rust_decimal_macros::dec;
+
+    use super::*;
+    use crate::engine::{
+        domain::{TransactionEvent, TransactionType},
+        error::LedgerError,
+        ledger::{Account, InMemoryLedger},
+    };
+
+    fn event(transaction_type: TransactionType, tx_id: u32, amount: u32) -> TransactionEvent {
+        TransactionEvent {
+            transaction_type,
+            client_id: 1,
+            tx_id,
+            amount: Some(amount.into()),
+            counterparty: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_submit() {
+        let ledger = InMemoryLedger::<Account>::new();
+        let engine = EngineHandle::spawn(Arc::clone(&ledger));
+
+        let snapshot = engine
+            .submit(event(TransactionType::Deposit, 1, 10))
+            .await
+            .unwrap();
+        assert_eq!(snapshot.available, dec!(10));
+        assert!(matches!(
+            engine
+                .submit(event(TransactionType::Withdrawal, 2, 25))
+                .await,
+            Err(StoreError::Rejected(LedgerError::InsufficientFunds { .. }))
+        ));
+
+        // Submissions are applied in order even when awaited concurrently.
+        let results = join_all(
+            (3..13).map(|tx_id| engine.submit(event(TransactionType::Withdrawal, tx_id, 1))),
+        )
+        .await;
+        let available = results
+            .into_iter()
+            .map(|result| result.unwrap().available)
+            .collect::<Vec<_>>();
+        assert_eq!(available, (0..10).rev().map(Into::into).collect::<Vec<_>>());
+        assert_eq!(ledger.snapshot(1).await.unwrap().total, dec!(0));
+    }
+}
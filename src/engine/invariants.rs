@@ -0,0 +1,170 @@
+use std::fmt;
+
+use crate::engine::{
+    bookkeeping::{LedgerAccount, TrialBalance},
+    domain::Balance,
+    ledger::Account,
+};
+
+/// A client whose live balance differs from what its recorded transactions, or the books,
+/// add up to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mismatch {
+    pub client_id: u16,
+    /// The balance recomputed with [`Account::recompute`], or read from the books.
+    pub expected: Balance,
+    /// The live balance of the account.
+    pub actual: Balance,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total = |balance: &Balance| balance.available + balance.held;
+        write!(
+            f,
+            "Client `{}`: available {} (expected {}), held {} (expected {}), total {} (expected {})",
+            self.client_id,
+            self.actual.available,
+            self.expected.available,
+            self.actual.held,
+            self.expected.held,
+            total(&self.actual),
+            total(&self.expected),
+        )
+    }
+}
+
+/// Recomputes the balance of every account from its recorded transactions and dispute
+/// states, and returns the accounts whose live balance differs, in the given order.
+pub fn check<'a, I>(accounts: I) -> Vec<Mismatch>
+where
+    I: IntoIterator<Item = (u16, &'a Account)>,
+{
+    accounts
+        .into_iter()
+        .filter_map(|(client_id, account)| {
+            let expected = account.recompute();
+            let actual = account.balance();
+            (expected != *actual).then(|| Mismatch {
+                client_id,
+                expected,
+                actual: actual.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Compares the live balance of every account with the balances of its client accounts in
+/// the books of all `accounts`, and returns the accounts that differ, in the given order.
+///
+/// Every transaction balances in the books, so this catches the ones that were applied to
+/// the accounts differently than they were posted.
+pub fn check_books<'a, I>(accounts: I) -> Vec<Mismatch>
+where
+    I: IntoIterator<Item = (u16, &'a Account)>,
+{
+    let accounts = accounts.into_iter().collect::<Vec<_>>();
+    let books = accounts
+        .iter()
+        .map(|(_, account)| account.book())
+        .collect::<TrialBalance>();
+    accounts
+        .into_iter()
+        .filter_map(|(client_id, account)| {
+            // Client accounts have credit balances.
+            let expected = Balance {
+                available: -books.balance(&LedgerAccount::Available(client_id)),
+                held: -books.balance(&LedgerAccount::Held(client_id)),
+            };
+            let actual = account.balance();
+            (expected != *actual).then(|| Mismatch {
+                client_id,
+                expected,
+                actual: actual.clone(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use rust_decimal_macros::dec;
+
+    use super::*;
+    use crate::engine::{
+        config::AccountConfig,
+        domain::{TransactionEvent, TransactionType},
+    };
+
+    #[test]
+    fn test_check() {
+        let config = AccountConfig {
+            allow_negative_available: true,
+            ..AccountConfig::default()
+        };
+        let event = |transaction_type, tx_id, amount| TransactionEvent {
+            client_id: 1,
+            tx_id,
+            transaction_type,
+            amount,
+            counterparty: None,
+        };
+        let mut account = Account::new(
+            1,
+            event(TransactionType::Deposit, 1, Some(dec!(10))),
+            &config,
+        );
+        for (tx_id, event) in [
+            (2, event(TransactionType::Deposit, 2, Some(dec!(5)))),
+            (3, event(TransactionType::Withdrawal, 3, Some(dec!(12)))),
+            (4, event(TransactionType::Interest, 4, Some(dec!(1)))),
+            (1, event(TransactionType::Dispute, 1, None)),
+            (2, event(TransactionType::Dispute, 2, None)),
+            (2, event(TransactionType::Chargeback, 2, None)),
+        ] {
+            account.apply_tx(tx_id, event, &config).unwrap();
+        }
+        assert_eq!(account.balance().available, dec!(-11));
+        assert_eq!(account.balance().held, dec!(10));
+        assert_eq!(check([(1, &account)]), Vec::new());
+
+        // A first withdrawal is not applied, so it must not be recorded either.
+        let empty = Account::new(
+            2,
+            event(TransactionType::Withdrawal, 2, Some(dec!(3))),
+            &config,
+        );
+        assert_eq!(check([(2, &empty)]), Vec::new());
+    }
+
+    #[test]
+    fn test_check_books() {
+        let config = AccountConfig {
+            double_entry: true,
+            ..AccountConfig::default()
+        };
+        let event = |client_id, transaction_type, tx_id, counterparty| TransactionEvent {
+            client_id,
+            tx_id,
+            transaction_type,
+            amount: Some(dec!(10)),
+            counterparty,
+        };
+        let mut sender = Account::new(1, event(1, TransactionType::Deposit, 1, None), &config);
+        let receiver = Account::new(2, event(2, TransactionType::Deposit, 2, None), &config);
+        assert_eq!(check_books([(1, &sender), (2, &receiver)]), Vec::new());
+
+        // A transfer posted by the sender, but never applied to the receiver.
+        sender
+            .apply_tx(3, event(1, TransactionType::Transfer, 3, Some(2)), &config)
+            .unwrap();
+        assert_eq!(
+            check_books([(1, &sender), (2, &receiver)]),
+            vec![Mismatch {
+                client_id: 2,
+                expected: Balance::new(dec!(20)),
+                actual: Balance::new(dec!(10)),
+            }]
+        );
+    }
+}
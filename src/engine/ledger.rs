@@ -1,18 +1,26 @@
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{HashMap, HashSet, VecDeque},
     hash::Hash,
     sync::Arc,
 };
 
 use futures::future::BoxFuture;
 use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
 use tokio::sync::Mutex;
 
 use crate::engine::{
+    bookkeeping::{Book, LedgerAccount, Posting},
+    config::AccountConfig,
     domain::{AccountSnapshot, Balance, TransactionEvent, TransactionType},
-    error::LedgerError,
+    error::{LedgerError, StoreError},
+    limits::ClientLimits,
+    rules::Verdict,
 };
 
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
 const MAX_DECIMAL_PLACES: u32 = 4;
 
 pub trait Aggregate {
@@ -24,37 +32,168 @@ pub trait Aggregate {
     fn new(id: Self::TxID, tx_data: Self::EventData) -> Self;
     fn apply_tx(&mut self, tx_id: Self::TxID, tx_data: Self::EventData) -> Result<(), Self::Error>;
     fn snapshot(&self, client_id: Self::ID) -> Self::Snapshot;
+    /// Returns the transactions the aggregate keeps a record of, if any.
+    fn transactions(&self) -> Vec<Self::EventData> {
+        Vec::new()
+    }
+    /// The configuration an [`InMemoryLedger`] applies transactions with unless it is given
+    /// one. `()` applies them with [`new`](Self::new) and [`apply_tx`](Self::apply_tx).
+    fn ledger_config() -> Box<dyn LedgerConfig<Self> + Send + Sync>
+    where
+        Self: Sized + 'static,
+    {
+        Box::new(())
+    }
 }
 
-pub trait Ledger<A> {
-    type Error;
+/// An event that names the aggregate it applies to and its transaction, so that it can be
+/// dispatched to a [`Ledger`].
+pub trait Route<ID, TxID> {
+    fn route(&self) -> (ID, TxID);
+}
+
+/// How a ledger applies transactions to aggregates of type `A`.
+///
+/// `()` applies every transaction to its own aggregate alone, see [`AccountConfig`] for one
+/// that also applies transfers to their receiver and charges fees.
+pub trait LedgerConfig<A: Aggregate> {
+    /// Applies `tx_data` to the aggregate `id` in `aggregates`, creating it if it does not
+    /// exist yet, and returns the rules that flagged the transaction.
+    fn apply(
+        &self,
+        aggregates: &mut HashMap<A::ID, A>,
+        id: A::ID,
+        tx_id: A::TxID,
+        tx_data: A::EventData,
+    ) -> LedgerResult<A, Vec<String>> {
+        match aggregates.get_mut(&id) {
+            Some(aggregate) => aggregate
+                .apply_tx(tx_id, tx_data)
+                .map_err(StoreError::Rejected)?,
+            None => {
+                aggregates.insert(id, A::new(tx_id, tx_data));
+            }
+        }
+        Ok(Vec::new())
+    }
+}
+
+impl<A: Aggregate> LedgerConfig<A> for () {}
+
+/// Aggregates whose transactions may apply to several of them at once, e.g. a transfer
+/// between two accounts. Such a transaction is applied to all of them or to none.
+pub trait Counterparty: Aggregate + Sized {
+    type Config;
+    /// Applies `tx_data` like [`Aggregate::apply_tx`], according to `config`.
+    fn apply_configured(
+        &mut self,
+        tx_id: Self::TxID,
+        tx_data: Self::EventData,
+        config: &Self::Config,
+    ) -> Result<(), Self::Error>;
+    /// Returns the other aggregates `tx_data` applies to, if it moves value between them.
+    fn counterparties(tx_data: &Self::EventData, config: &Self::Config) -> Vec<Self::ID>;
+    /// Creates the empty aggregate `id` for a transaction with
+    /// [`counterparties`](Self::counterparties) to apply to, if it may be created that way.
+    fn open(id: &Self::ID, tx_data: &Self::EventData, config: &Self::Config) -> Option<Self>;
+    /// The error of a transaction with [`counterparties`](Self::counterparties) that
+    /// applies to the aggregate `id`, which neither exists nor may be opened.
+    fn missing(id: &Self::ID, tx_data: &Self::EventData) -> Self::Error;
+    /// Checks whether the counterparty `id` would accept its side of a transaction, without
+    /// changing the aggregate.
+    fn check_counterparty_tx(
+        &self,
+        id: &Self::ID,
+        tx_id: &Self::TxID,
+        tx_data: &Self::EventData,
+        config: &Self::Config,
+    ) -> Result<(), Self::Error>;
+    /// Applies the side of a transaction that falls to this aggregate, the counterparty `id`,
+    /// once [`check_counterparty_tx`](Self::check_counterparty_tx) accepted it. It cannot
+    /// fail, as the other aggregates have already been updated by then.
+    fn apply_counterparty_tx(
+        &mut self,
+        id: Self::ID,
+        tx_id: Self::TxID,
+        tx_data: Self::EventData,
+        config: &Self::Config,
+    );
+}
+
+/// A transaction the aggregate refused to apply.
+pub struct Rejection<A: Aggregate> {
+    pub id: <A as Aggregate>::ID,
+    pub tx_id: <A as Aggregate>::TxID,
+    pub event: <A as Aggregate>::EventData,
+    pub error: <A as Aggregate>::Error,
+}
+
+/// A transaction a [`Ledger`] applied.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Applied<ID> {
+    /// The aggregate the transaction applied to.
+    pub id: ID,
+    /// The rules that flagged the transaction, see [`LedgerConfig::apply`].
+    pub flags: Vec<String>,
+}
 
+/// Result of a [`Ledger`] operation on aggregates of type `A`.
+pub type LedgerResult<A, T> = Result<T, StoreError<<A as Aggregate>::ID, <A as Aggregate>::Error>>;
+
+/// Every aggregate of a [`Ledger`] with its ID.
+pub type Aggregates<A> = Vec<(<A as Aggregate>::ID, A)>;
+
+pub trait Ledger<A: Aggregate> {
+    /// Applies `transaction` to the aggregate `id`, creating it if it does not exist yet.
+    ///
+    /// Fails with [`StoreError::Rejected`] if the aggregate refuses the transaction.
     fn process_transaction(
         self: Arc<Self>,
         id: <A as Aggregate>::ID,
         tx_id: <A as Aggregate>::TxID,
         transaction: <A as Aggregate>::EventData,
-    ) -> BoxFuture<'static, Result<<A as Aggregate>::ID, Self::Error>>
+    ) -> BoxFuture<'static, LedgerResult<A, Applied<<A as Aggregate>::ID>>>
     where
-        A: Aggregate + Send + Sync + 'static,
+        A: Send + Sync + 'static,
         <A as Aggregate>::TxID: Clone,
-        <A as Aggregate>::EventData: Clone,
-        <A as Aggregate>::Error: std::fmt::Display;
+        <A as Aggregate>::EventData: Clone;
 
+    /// Fails with [`StoreError::NotFound`] if the aggregate `id` does not exist.
     fn snapshot(
         self: Arc<Self>,
         id: <A as Aggregate>::ID,
-    ) -> BoxFuture<'static, Result<<A as Aggregate>::Snapshot, Self::Error>>
+    ) -> BoxFuture<'static, LedgerResult<A, <A as Aggregate>::Snapshot>>
     where
-        A: Aggregate + Send + Sync + 'static,
+        A: Send + Sync + 'static,
         <A as Aggregate>::ID: Clone;
 
     fn all_snapshots(
         self: Arc<Self>,
-    ) -> BoxFuture<'static, Result<Vec<<A as Aggregate>::Snapshot>, Self::Error>>
+    ) -> BoxFuture<'static, LedgerResult<A, Vec<<A as Aggregate>::Snapshot>>>
     where
-        A: Aggregate + Send + Sync + 'static,
+        A: Send + Sync + 'static,
         <A as Aggregate>::ID: Clone;
+
+    /// Fails with [`StoreError::NotFound`] if the aggregate `id` does not exist, and with
+    /// [`StoreError::Unsupported`] unless the ledger keeps the transactions.
+    fn transactions(
+        self: Arc<Self>,
+        _id: <A as Aggregate>::ID,
+    ) -> BoxFuture<'static, LedgerResult<A, Vec<<A as Aggregate>::EventData>>>
+    where
+        A: Send + Sync + 'static,
+    {
+        Box::pin(async { Err(StoreError::Unsupported("transactions")) })
+    }
+
+    /// Returns a copy of every aggregate, sorted by ID. Fails with
+    /// [`StoreError::Unsupported`] unless the ledger can copy them.
+    fn aggregates(self: Arc<Self>) -> BoxFuture<'static, LedgerResult<A, Aggregates<A>>>
+    where
+        A: Clone + Send + Sync + 'static,
+    {
+        Box::pin(async { Err(StoreError::Unsupported("aggregates")) })
+    }
 }
 
 pub struct InMemoryLedger<A>
@@ -62,100 +201,381 @@ where
     A: Aggregate + Clone + Send + Sync + 'static,
 {
     view: Mutex<HashMap<<A as Aggregate>::ID, A>>,
+    config: Box<dyn LedgerConfig<A> + Send + Sync>,
 }
 
 impl<A> InMemoryLedger<A>
 where
     A: Aggregate + Clone + Send + Sync + 'static,
 {
+    /// Creates a ledger that applies transactions with [`Aggregate::ledger_config`].
     pub fn new() -> Arc<Self> {
         Arc::new(Self {
             view: Mutex::new(HashMap::new()),
+            config: A::ledger_config(),
+        })
+    }
+
+    /// Creates a ledger that applies transactions according to `config`.
+    pub fn with_config<C>(config: C) -> Arc<Self>
+    where
+        C: LedgerConfig<A> + Send + Sync + 'static,
+    {
+        Self::with_aggregates(Vec::new(), config)
+    }
+
+    /// Creates a ledger that starts from `aggregates`, e.g. restored from a checkpoint.
+    pub fn with_aggregates<C>(aggregates: Aggregates<A>, config: C) -> Arc<Self>
+    where
+        C: LedgerConfig<A> + Send + Sync + 'static,
+    {
+        Arc::new(Self {
+            view: Mutex::new(aggregates.into_iter().collect()),
+            config: Box::new(config),
         })
     }
 }
 
+/// Applies `transaction` to the aggregates `id` and `counterparties` in `view`, or to none
+/// of them. The counterparties are checked before anything changes and cannot refuse their
+/// side afterwards, so that every aggregate is updated in place and nothing has to be
+/// rolled back.
+fn post<A>(
+    view: &mut HashMap<<A as Aggregate>::ID, A>,
+    id: <A as Aggregate>::ID,
+    counterparties: &[<A as Aggregate>::ID],
+    tx_id: <A as Aggregate>::TxID,
+    transaction: <A as Aggregate>::EventData,
+    config: &<A as Counterparty>::Config,
+) -> LedgerResult<A, ()>
+where
+    A: Counterparty,
+    <A as Aggregate>::EventData: Clone,
+{
+    let mut opened = Vec::new();
+    for to in counterparties {
+        let checked = match view.get(to) {
+            Some(other) => other.check_counterparty_tx(to, &tx_id, &transaction, config),
+            None => match A::open(to, &transaction, config) {
+                Some(other) => {
+                    let checked = other.check_counterparty_tx(to, &tx_id, &transaction, config);
+                    opened.push((to.clone(), other));
+                    checked
+                }
+                None => return Err(StoreError::Rejected(A::missing(to, &transaction))),
+            },
+        };
+        checked.map_err(StoreError::Rejected)?;
+    }
+    let applied = match view.get_mut(&id) {
+        Some(sender) => sender.apply_configured(tx_id.clone(), transaction.clone(), config),
+        None => {
+            let mut sender = A::open(&id, &transaction, config)
+                .ok_or_else(|| StoreError::Rejected(A::missing(&id, &transaction)))?;
+            let applied = sender.apply_configured(tx_id.clone(), transaction.clone(), config);
+            // Refusing the transaction may still have changed the new aggregate, e.g. locked it.
+            view.insert(id, sender);
+            applied
+        }
+    };
+    applied.map_err(StoreError::Rejected)?;
+    view.extend(opened);
+    for to in counterparties {
+        if let Some(other) = view.get_mut(to) {
+            other.apply_counterparty_tx(to.clone(), tx_id.clone(), transaction.clone(), config);
+        }
+    }
+    Ok(())
+}
+
 impl<A> Ledger<A> for InMemoryLedger<A>
 where
     A: Aggregate + Clone + Send + Sync + 'static,
+    <A as Aggregate>::ID: Ord,
+    <A as Aggregate>::Error: Send,
 {
-    type Error = ();
-
     fn process_transaction(
         self: Arc<Self>,
         id: <A as Aggregate>::ID,
         tx_id: <A as Aggregate>::TxID,
         transaction: <A as Aggregate>::EventData,
-    ) -> BoxFuture<'static, Result<<A as Aggregate>::ID, Self::Error>>
+    ) -> BoxFuture<'static, LedgerResult<A, Applied<<A as Aggregate>::ID>>>
     where
-        A: Aggregate + Send + Sync + 'static,
+        A: Send + Sync + 'static,
         <A as Aggregate>::TxID: Clone,
         <A as Aggregate>::EventData: Clone,
-        <A as Aggregate>::Error: std::fmt::Display,
     {
         Box::pin(async move {
-            self.view
-                .lock()
-                .await
-                .entry(id.clone())
-                .and_modify(|account| {
-                    if let Err(err) = account.apply_tx(tx_id.clone(), transaction.clone()) {
-                        eprintln!("Error processing transaction: {err}");
-                    }
-                })
-                .or_insert_with(|| <A as Aggregate>::new(tx_id, transaction));
-            Ok(id)
+            let mut view = self.view.lock().await;
+            let flags = self
+                .config
+                .apply(&mut view, id.clone(), tx_id, transaction)?;
+            Ok(Applied { id, flags })
         })
     }
 
     fn snapshot(
         self: Arc<Self>,
         id: <A as Aggregate>::ID,
-    ) -> BoxFuture<'static, Result<<A as Aggregate>::Snapshot, Self::Error>>
+    ) -> BoxFuture<'static, LedgerResult<A, <A as Aggregate>::Snapshot>>
     where
-        A: Aggregate + Send + Sync + 'static,
+        A: Send + Sync + 'static,
         <A as Aggregate>::ID: Clone,
     {
         Box::pin(async move {
             match self.view.lock().await.get(&id) {
                 Some(view) => Ok(view.snapshot(id)),
-                None => Err(()),
+                None => Err(StoreError::NotFound(id)),
             }
         })
     }
 
     fn all_snapshots(
         self: Arc<Self>,
-    ) -> BoxFuture<'static, Result<Vec<<A as Aggregate>::Snapshot>, Self::Error>>
+    ) -> BoxFuture<'static, LedgerResult<A, Vec<<A as Aggregate>::Snapshot>>>
     where
-        A: Aggregate + Send + Sync + 'static,
+        A: Send + Sync + 'static,
         <A as Aggregate>::ID: Clone,
     {
         Box::pin(async move {
-            Ok(self
-                .view
-                .lock()
-                .await
-                .iter()
+            let view = self.view.lock().await;
+            let mut entries = view.iter().collect::<Vec<_>>();
+            // Sort by ID so that identical input always produces identical output.
+            entries.sort_by_key(|(id, _)| *id);
+            Ok(entries
+                .into_iter()
                 .map(|(id, entry)| entry.snapshot(id.clone()))
                 .collect::<Vec<_>>())
         })
     }
+
+    fn transactions(
+        self: Arc<Self>,
+        id: <A as Aggregate>::ID,
+    ) -> BoxFuture<'static, LedgerResult<A, Vec<<A as Aggregate>::EventData>>>
+    where
+        A: Send + Sync + 'static,
+    {
+        Box::pin(async move {
+            match self.view.lock().await.get(&id) {
+                Some(view) => Ok(view.transactions()),
+                None => Err(StoreError::NotFound(id)),
+            }
+        })
+    }
+
+    fn aggregates(self: Arc<Self>) -> BoxFuture<'static, LedgerResult<A, Aggregates<A>>>
+    where
+        A: Clone + Send + Sync + 'static,
+    {
+        Box::pin(async move {
+            let view = self.view.lock().await;
+            let mut aggregates = view
+                .iter()
+                .map(|(id, aggregate)| (id.clone(), aggregate.clone()))
+                .collect::<Vec<_>>();
+            aggregates.sort_by(|(a, _), (b, _)| a.cmp(b));
+            Ok(aggregates)
+        })
+    }
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Account {
     balance: Balance,
     transactions: HashMap<u32, TransactionEvent>,
     disputed_transactions: HashSet<u32>,
+    charged_back_transactions: HashSet<u32>,
+    /// What fees, interest and other postings that are not recorded as transactions added
+    /// to the available funds.
+    adjustments: Decimal,
     previous_tx_id: u32,
     locked: bool,
+    /// The latest applied transactions, as far back as the rules look.
+    recent: VecDeque<TransactionEvent>,
+    /// The rules that flagged the latest applied transaction.
+    #[serde(skip)]
+    flags: Vec<String>,
+    /// The postings of the applied transactions, if the ledger keeps double-entry books.
+    #[serde(skip)]
+    book: Book,
 }
 
 impl Account {
+    fn empty() -> Self {
+        Account {
+            balance: Balance::default(),
+            transactions: HashMap::new(),
+            disputed_transactions: HashSet::new(),
+            charged_back_transactions: HashSet::new(),
+            adjustments: Decimal::default(),
+            previous_tx_id: 0,
+            locked: false,
+            recent: VecDeque::new(),
+            flags: Vec::new(),
+            book: Book::default(),
+        }
+    }
+
+    /// Starts the books of the account `id` over from its balance, as if it was funded at
+    /// once. Used for accounts restored from storage, which do not keep their postings.
+    pub(crate) fn open_books(&mut self, id: u16) {
+        self.book = Book::default();
+        self.book.post(vec![
+            Posting::debit(
+                LedgerAccount::Funding,
+                self.balance.available + self.balance.held,
+            ),
+            Posting::credit(LedgerAccount::Available(id), self.balance.available),
+            Posting::credit(LedgerAccount::Held(id), self.balance.held),
+        ]);
+    }
+
+    /// The double-entry books of the transactions applied to the account.
+    pub fn book(&self) -> &Book {
+        &self.book
+    }
+
+    pub fn balance(&self) -> &Balance {
+        &self.balance
+    }
+
+    /// The balance the recorded transactions, their dispute states and the postings that
+    /// are not recorded as transactions add up to. Equal to [`balance`](Self::balance)
+    /// unless the account was updated incorrectly.
+    pub fn recompute(&self) -> Balance {
+        let mut balance = Balance::new(self.adjustments);
+        for (tx_id, event) in &self.transactions {
+            let amount = match event.amount {
+                Some(amount) => amount,
+                None => continue,
+            };
+            match event.transaction_type {
+                TransactionType::Deposit => balance.available += amount,
+                TransactionType::Withdrawal | TransactionType::Transfer => {
+                    balance.available -= amount
+                }
+                _ => {}
+            }
+            if self.disputed_transactions.contains(tx_id) {
+                balance.available -= amount;
+                balance.held += amount;
+            } else if self.charged_back_transactions.contains(tx_id) {
+                balance.available -= amount;
+            }
+        }
+        balance
+    }
+
+    /// The postings of applying `tx_data`, with its `fee`, to the account as it is now.
+    /// Empty if the transaction would not change any balance.
+    fn postings(
+        &self,
+        tx_data: &TransactionEvent,
+        fee: Decimal,
+        config: &AccountConfig,
+    ) -> Vec<Posting> {
+        use LedgerAccount::{Available, ChargebackLoss, Funding, Held};
+
+        let client = tx_data.client_id;
+        let house = match config.house {
+            Some(house) => Available(house),
+            None => Funding,
+        };
+        let disputed = self
+            .transactions
+            .get(&tx_data.tx_id)
+            .and_then(|disputed| disputed.amount);
+        let mut postings = match (&tx_data.transaction_type, tx_data.amount, disputed) {
+            (TransactionType::Deposit, Some(amount), _) => vec![
+                Posting::debit(Funding, amount),
+                Posting::credit(Available(client), amount - fee),
+            ],
+            (TransactionType::Withdrawal, Some(amount), _) => vec![
+                Posting::debit(Available(client), amount + fee),
+                Posting::credit(Funding, amount),
+            ],
+            (TransactionType::Transfer, Some(amount), _) => match tx_data.counterparty {
+                Some(to) => vec![
+                    Posting::debit(Available(client), amount + fee),
+                    Posting::credit(Available(to), amount),
+                ],
+                None => Vec::new(),
+            },
+            (TransactionType::Fee, Some(amount), _) => vec![
+                Posting::debit(Available(client), amount),
+                Posting::credit(house, amount),
+            ],
+            (TransactionType::Interest, Some(amount), _) => vec![
+                Posting::debit(house, amount),
+                Posting::credit(Available(client), amount),
+            ],
+            (TransactionType::Dispute, _, Some(amount)) => vec![
+                Posting::debit(Available(client), amount),
+                Posting::credit(Held(client), amount),
+            ],
+            (TransactionType::Resolve, _, Some(amount)) if self.balance.held >= amount => vec![
+                Posting::debit(Held(client), amount),
+                Posting::credit(Available(client), amount),
+            ],
+            (TransactionType::Chargeback, _, Some(amount)) if self.balance.held >= amount => vec![
+                Posting::debit(Held(client), amount),
+                Posting::credit(ChargebackLoss, amount),
+            ],
+            _ => Vec::new(),
+        };
+        if !fee.is_zero() {
+            postings.push(Posting::credit(house, fee));
+        }
+        postings
+    }
+
+    /// The fee charged alongside `tx_data`. Only charged when there is a house to collect
+    /// it, and never to the house itself.
+    fn fee(tx_data: &TransactionEvent, config: &AccountConfig) -> Decimal {
+        match (&tx_data.transaction_type, config.house) {
+            (
+                TransactionType::Deposit | TransactionType::Withdrawal | TransactionType::Transfer,
+                Some(house),
+            ) if house != tx_data.client_id => {
+                config.fees.fee(tx_data).round_dp(MAX_DECIMAL_PLACES)
+            }
+            _ => Decimal::default(),
+        }
+    }
+
+    fn remember(&mut self, tx_data: TransactionEvent, window: usize) {
+        self.recent.push_back(tx_data);
+        while self.recent.len() > window {
+            self.recent.pop_front();
+        }
+    }
+
+    /// Returns the rules that flag `tx_data`, unless one refuses it.
+    fn check_rules(
+        &mut self,
+        tx_data: &TransactionEvent,
+        config: &AccountConfig,
+    ) -> Result<Vec<String>, LedgerError> {
+        match config.rules.evaluate(&self.recent, tx_data) {
+            Verdict::Allow(flags) => Ok(flags.into_iter().map(str::to_owned).collect()),
+            Verdict::Reject(rule) => Err(LedgerError::RuleViolation {
+                rule: rule.to_owned(),
+                tx_id: tx_data.tx_id,
+            }),
+            Verdict::Freeze(rule) => {
+                self.locked = true;
+                Err(LedgerError::AccountFrozen {
+                    rule: rule.to_owned(),
+                    tx_id: tx_data.tx_id,
+                })
+            }
+        }
+    }
+
     fn record_tx(&mut self, tx_id: u32, tx_data: TransactionEvent) {
         self.transactions.insert(tx_id, tx_data);
-        self.previous_tx_id = tx_id;
+        self.previous_tx_id = self.previous_tx_id.max(tx_id);
     }
 
     fn get_tx(&self, tx_id: u32) -> Result<&TransactionEvent, LedgerError> {
@@ -165,24 +585,74 @@ impl Account {
     }
 
     fn check_tx_id(&self, tx_id: u32) -> Result<(), LedgerError> {
-        if self.previous_tx_id < tx_id {
+        if self.transactions.is_empty() || self.previous_tx_id < tx_id {
             Ok(())
         } else {
             Err(LedgerError::SuspiciousTransaction(tx_id))
         }
     }
 
-    fn check_available_amount(&self, tx_amount: Decimal) -> Result<(), LedgerError> {
-        if self.balance.available >= tx_amount {
-            Ok(())
-        } else {
-            Err(LedgerError::InsufficientFunds {
+    fn check_available_amount(
+        &self,
+        tx_amount: Decimal,
+        overdraft: Option<Decimal>,
+    ) -> Result<(), LedgerError> {
+        match overdraft {
+            Some(overdraft) if self.balance.available + overdraft < tx_amount => {
+                Err(LedgerError::OverdraftExceeded {
+                    available: self.balance.available,
+                    overdraft,
+                    amount: tx_amount,
+                })
+            }
+            Some(_) => Ok(()),
+            None if self.balance.available >= tx_amount => Ok(()),
+            None => Err(LedgerError::InsufficientFunds {
                 available: self.balance.available,
                 amount: tx_amount,
-            })
+            }),
         }
     }
 
+    fn check_withdrawal_limits(
+        &self,
+        tx_amount: Decimal,
+        limits: &ClientLimits,
+    ) -> Result<(), LedgerError> {
+        if let Some(limit) = limits.max_withdrawal {
+            if tx_amount > limit {
+                return Err(LedgerError::WithdrawalLimitExceeded {
+                    limit,
+                    amount: tx_amount,
+                });
+            }
+        }
+        if let Some((cap, window)) = limits.rolling_cap() {
+            let withdrawn = self
+                .recent
+                .iter()
+                .rev()
+                .take(window.saturating_sub(1))
+                .filter(|recent| {
+                    matches!(
+                        recent.transaction_type,
+                        TransactionType::Withdrawal | TransactionType::Transfer
+                    )
+                })
+                .filter_map(|recent| recent.amount)
+                .sum::<Decimal>()
+                + tx_amount;
+            if withdrawn > cap {
+                return Err(LedgerError::WithdrawalCapExceeded {
+                    cap,
+                    window,
+                    withdrawn,
+                });
+            }
+        }
+        Ok(())
+    }
+
     fn check_disputed_transaction(&self, tx_id: u32, expected: bool) -> Result<(), LedgerError> {
         if self.disputed_transactions.contains(&tx_id) != expected {
             Err(LedgerError::DisputedTransaction(tx_id))
@@ -200,58 +670,166 @@ impl Account {
     }
 }
 
-impl Aggregate for Account {
-    type Error = LedgerError;
-    type ID = u16;
-    type TxID = u32;
-    type EventData = TransactionEvent;
-    type Snapshot = AccountSnapshot;
+impl Account {
+    /// Creates the account from its first transaction, applied according to `config`.
+    pub fn new(id: u32, tx_data: TransactionEvent, config: &AccountConfig) -> Self {
+        Self::create(id, tx_data, config).0
+    }
 
-    fn new(id: Self::TxID, tx_data: Self::EventData) -> Self {
-        let balance = match tx_data.transaction_type {
-            TransactionType::Deposit => Balance::new(tx_data.amount.unwrap()),
-            _ => Balance::default(),
-        };
-        let mut account = Account {
-            balance,
-            transactions: HashMap::new(),
-            disputed_transactions: HashSet::new(),
-            previous_tx_id: id,
-            locked: false,
-        };
-        account.record_tx(id, tx_data);
-        account
+    /// Creates the account from its first transaction, and whether that transaction was
+    /// applied. The account exists either way, as refusing it may still change the account,
+    /// e.g. lock it.
+    pub fn create(
+        id: u32,
+        tx_data: TransactionEvent,
+        config: &AccountConfig,
+    ) -> (Self, Result<(), LedgerError>) {
+        let mut account = Account::empty();
+        let result = account.apply_tx(id, tx_data, config);
+        (account, result)
     }
 
-    fn apply_tx(&mut self, tx_id: Self::TxID, tx_data: Self::EventData) -> Result<(), Self::Error> {
+    /// Applies `tx_data` according to `config`. Rules only see transactions that are
+    /// otherwise valid, so that a rejected transaction cannot freeze the account.
+    pub fn apply_tx(
+        &mut self,
+        tx_id: u32,
+        tx_data: TransactionEvent,
+        config: &AccountConfig,
+    ) -> Result<(), LedgerError> {
+        self.flags.clear();
         self.locked_account(tx_id)?;
+        let fee = Self::fee(&tx_data, config);
+        let postings = if config.double_entry {
+            let postings = self.postings(&tx_data, fee, config);
+            Book::check(tx_id, &postings)?;
+            Some(postings)
+        } else {
+            None
+        };
+        self.check_tx(tx_id, &tx_data, fee, config)?;
+        let flags = self.check_rules(&tx_data, config)?;
+        let window = config.history(tx_data.client_id);
+        let remembered = (window > 0).then(|| tx_data.clone());
+
+        self.post_tx(tx_id, tx_data, fee);
+        if let Some(postings) = postings {
+            self.book.post(postings);
+        }
+        if let Some(tx_data) = remembered {
+            self.remember(tx_data, window);
+        }
+        self.flags = flags;
+        Ok(())
+    }
+
+    /// Checks that `tx_data` can be applied, without changing the account.
+    fn check_tx(
+        &self,
+        tx_id: u32,
+        tx_data: &TransactionEvent,
+        fee: Decimal,
+        config: &AccountConfig,
+    ) -> Result<(), LedgerError> {
+        if !config.allow_out_of_order
+            && matches!(
+                tx_data.transaction_type,
+                TransactionType::Deposit | TransactionType::Withdrawal | TransactionType::Transfer
+            )
+        {
+            self.check_tx_id(tx_id)?;
+        }
 
         match tx_data.transaction_type {
             TransactionType::Deposit => {
-                self.check_tx_id(tx_id)?;
                 let tx_amount = tx_data.amount.ok_or(LedgerError::MissingAmount(tx_id))?;
-                self.balance.available += tx_amount;
-                self.record_tx(tx_id, tx_data);
+                if fee > tx_amount {
+                    return Err(LedgerError::FeeExceedsAmount {
+                        tx_id,
+                        fee,
+                        amount: tx_amount,
+                    });
+                }
             }
-            TransactionType::Withdrawal => {
-                self.check_tx_id(tx_id)?;
+            TransactionType::Withdrawal | TransactionType::Transfer => {
                 let tx_amount = tx_data.amount.ok_or(LedgerError::MissingAmount(tx_id))?;
-                self.check_available_amount(tx_amount)?;
-                self.balance.available -= tx_amount;
-                self.record_tx(tx_id, tx_data);
+                if tx_data.transaction_type == TransactionType::Transfer
+                    && !matches!(tx_data.counterparty, Some(to) if to != tx_data.client_id)
+                {
+                    return Err(LedgerError::InvalidCounterparty(tx_id));
+                }
+                // Limits apply to `tx_amount` alone.
+                let limits = config.limits.get(tx_data.client_id);
+                if let Some(limits) = limits {
+                    self.check_withdrawal_limits(tx_amount, limits)?;
+                }
+                self.check_available_amount(
+                    tx_amount + fee,
+                    limits.and_then(|limits| limits.overdraft),
+                )?;
+            }
+            TransactionType::Fee | TransactionType::Interest
+                if config.house == Some(tx_data.client_id) =>
+            {
+                return Err(LedgerError::HousePosting(tx_id));
+            }
+            TransactionType::Fee | TransactionType::Interest => {
+                tx_data.amount.ok_or(LedgerError::MissingAmount(tx_id))?;
             }
             TransactionType::Dispute => {
                 self.check_disputed_transaction(tx_id, false)?;
-                if let Some(disputed_amount) = self.get_tx(tx_id)?.amount {
-                    self.check_available_amount(disputed_amount)?;
+                let disputed = self.get_tx(tx_id)?;
+                if disputed.transaction_type == TransactionType::Transfer {
+                    return Err(LedgerError::OutgoingTransferDispute(tx_id));
+                }
+                if let Some(disputed_amount) = disputed.amount {
+                    if !config.allow_negative_available {
+                        self.check_available_amount(disputed_amount, None)?;
+                    }
+                }
+            }
+            TransactionType::Resolve | TransactionType::Chargeback => {
+                self.check_disputed_transaction(tx_id, true)?;
+                self.get_tx(tx_id)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies `tx_data` once [`check_tx`](Self::check_tx) accepted it.
+    fn post_tx(&mut self, tx_id: u32, tx_data: TransactionEvent, fee: Decimal) {
+        // The check made sure that the amounts are there.
+        let tx_amount = tx_data.amount.unwrap_or_default();
+        let disputed_amount = self.transactions.get(&tx_id).and_then(|tx| tx.amount);
+        match tx_data.transaction_type {
+            TransactionType::Deposit => {
+                self.balance.available += tx_amount - fee;
+                self.adjustments -= fee;
+                self.record_tx(tx_id, tx_data);
+            }
+            TransactionType::Withdrawal | TransactionType::Transfer => {
+                self.balance.available -= tx_amount + fee;
+                self.adjustments -= fee;
+                self.record_tx(tx_id, tx_data);
+            }
+            // Postings are not recorded, as they cannot be disputed.
+            TransactionType::Fee => {
+                self.balance.available -= tx_amount;
+                self.adjustments -= tx_amount;
+            }
+            TransactionType::Interest => {
+                self.balance.available += tx_amount;
+                self.adjustments += tx_amount;
+            }
+            TransactionType::Dispute => {
+                if let Some(disputed_amount) = disputed_amount {
                     self.balance.available -= disputed_amount;
                     self.balance.held += disputed_amount;
                     self.disputed_transactions.insert(tx_id);
                 }
             }
             TransactionType::Resolve => {
-                self.check_disputed_transaction(tx_id, true)?;
-                if let Some(disputed_amount) = self.get_tx(tx_id)?.amount {
+                if let Some(disputed_amount) = disputed_amount {
                     if self.balance.held >= disputed_amount {
                         self.balance.held -= disputed_amount;
                         self.balance.available += disputed_amount;
@@ -260,17 +838,40 @@ impl Aggregate for Account {
                 }
             }
             TransactionType::Chargeback => {
-                self.check_disputed_transaction(tx_id, true)?;
-                if let Some(disputed_amount) = self.get_tx(tx_id)?.amount {
+                if let Some(disputed_amount) = disputed_amount {
                     if self.balance.held >= disputed_amount {
                         self.balance.held -= disputed_amount;
                         self.locked = true;
                         self.disputed_transactions.remove(&tx_id);
+                        self.charged_back_transactions.insert(tx_id);
                     }
                 }
             }
         }
-        Ok(())
+    }
+
+    /// Returns the names of the rules that flagged the transaction last applied to the
+    /// account, which applied it regardless.
+    pub fn flags(&self) -> Vec<String> {
+        self.flags.clone()
+    }
+}
+
+/// Applies transactions with the default [`AccountConfig`], see [`Account::apply_tx`] and
+/// [`InMemoryLedger::with_config`] for others.
+impl Aggregate for Account {
+    type Error = LedgerError;
+    type ID = u16;
+    type TxID = u32;
+    type EventData = TransactionEvent;
+    type Snapshot = AccountSnapshot;
+
+    fn new(id: Self::TxID, tx_data: Self::EventData) -> Self {
+        Account::new(id, tx_data, &AccountConfig::default())
+    }
+
+    fn apply_tx(&mut self, tx_id: Self::TxID, tx_data: Self::EventData) -> Result<(), Self::Error> {
+        Account::apply_tx(self, tx_id, tx_data, &AccountConfig::default())
     }
 
     fn snapshot(&self, id: Self::ID) -> Self::Snapshot {
@@ -282,6 +883,147 @@ impl Aggregate for Account {
             locked: self.locked,
         }
     }
+
+    fn transactions(&self) -> Vec<Self::EventData> {
+        let mut transactions = self.transactions.values().cloned().collect::<Vec<_>>();
+        transactions.sort_by_key(|event| event.tx_id);
+        transactions
+    }
+
+    fn ledger_config() -> Box<dyn LedgerConfig<Self> + Send + Sync> {
+        Box::new(AccountConfig::default())
+    }
+}
+
+impl Route<u16, u32> for TransactionEvent {
+    fn route(&self) -> (u16, u32) {
+        (self.client_id, self.tx_id)
+    }
+}
+
+/// Applies transfers to their receiver, and fees and interest to the house, alongside the
+/// client of the transaction.
+impl LedgerConfig<Account> for AccountConfig {
+    fn apply(
+        &self,
+        accounts: &mut HashMap<u16, Account>,
+        id: u16,
+        tx_id: u32,
+        tx_data: TransactionEvent,
+    ) -> LedgerResult<Account, Vec<String>> {
+        let counterparties = Account::counterparties(&tx_data, self);
+        match accounts.get_mut(&id) {
+            _ if !counterparties.is_empty() => {
+                post(accounts, id, &counterparties, tx_id, tx_data, self)?
+            }
+            Some(account) => account
+                .apply_tx(tx_id, tx_data, self)
+                .map_err(StoreError::Rejected)?,
+            None => {
+                let (account, result) = Account::create(tx_id, tx_data, self);
+                accounts.insert(id, account);
+                result.map_err(StoreError::Rejected)?;
+            }
+        }
+        Ok(accounts.get(&id).map(Account::flags).unwrap_or_default())
+    }
+}
+
+impl Counterparty for Account {
+    type Config = AccountConfig;
+
+    fn apply_configured(
+        &mut self,
+        tx_id: Self::TxID,
+        tx_data: Self::EventData,
+        config: &Self::Config,
+    ) -> Result<(), Self::Error> {
+        self.apply_tx(tx_id, tx_data, config)
+    }
+
+    fn counterparties(tx_data: &Self::EventData, config: &Self::Config) -> Vec<Self::ID> {
+        let mut ids = Vec::new();
+        if tx_data.transaction_type == TransactionType::Transfer {
+            ids.extend(tx_data.counterparty);
+        }
+        let posting = matches!(
+            tx_data.transaction_type,
+            TransactionType::Fee | TransactionType::Interest
+        ) || !Self::fee(tx_data, config).is_zero();
+        match config.house {
+            Some(house) if posting && house != tx_data.client_id && !ids.contains(&house) => {
+                ids.push(house)
+            }
+            _ => {}
+        }
+        ids
+    }
+
+    fn open(id: &Self::ID, tx_data: &Self::EventData, config: &Self::Config) -> Option<Self> {
+        // The receiver of a transfer has to exist already, and so does its sender.
+        let opens = match config.house {
+            Some(house) if house == *id => true,
+            _ => *id == tx_data.client_id && tx_data.transaction_type != TransactionType::Transfer,
+        };
+        opens.then(Self::empty)
+    }
+
+    fn missing(id: &Self::ID, tx_data: &Self::EventData) -> Self::Error {
+        LedgerError::UnknownCounterparty {
+            tx_id: tx_data.tx_id,
+            client: *id,
+        }
+    }
+
+    fn check_counterparty_tx(
+        &self,
+        id: &Self::ID,
+        tx_id: &Self::TxID,
+        tx_data: &Self::EventData,
+        _config: &Self::Config,
+    ) -> Result<(), Self::Error> {
+        if tx_data.transaction_type == TransactionType::Transfer
+            && tx_data.counterparty == Some(*id)
+        {
+            self.locked_account(*tx_id)?;
+            tx_data.amount.ok_or(LedgerError::MissingAmount(*tx_id))?;
+        }
+        Ok(())
+    }
+
+    fn apply_counterparty_tx(
+        &mut self,
+        id: Self::ID,
+        tx_id: Self::TxID,
+        tx_data: Self::EventData,
+        config: &Self::Config,
+    ) {
+        if tx_data.transaction_type == TransactionType::Transfer && tx_data.counterparty == Some(id)
+        {
+            // The check made sure that there is an amount.
+            let tx_amount = tx_data.amount.unwrap_or_default();
+            self.balance.available += tx_amount;
+            // Recorded as a deposit, so that the receiving client can dispute it like one.
+            let received = TransactionEvent {
+                client_id: id,
+                tx_id,
+                transaction_type: TransactionType::Deposit,
+                amount: Some(tx_amount),
+                counterparty: None,
+            };
+            self.record_tx(tx_id, received);
+        }
+        if config.house == Some(id) {
+            // The house collects fees and pays interest even while it is locked.
+            let amount = match tx_data.transaction_type {
+                TransactionType::Fee => tx_data.amount.unwrap_or_default(),
+                TransactionType::Interest => -tx_data.amount.unwrap_or_default(),
+                _ => Self::fee(&tx_data, config),
+            };
+            self.balance.available += amount;
+            self.adjustments += amount;
+        }
+    }
 }
 
 #[cfg(test)]
@@ -289,6 +1031,7 @@ mod tests {
     use rust_decimal_macros::dec;
 
     use super::*;
+    use crate::engine::{bookkeeping::TrialBalance, fees::Fee, invariants};
 
     #[test]
     fn test_initial_deposit() {
@@ -297,9 +1040,10 @@ mod tests {
             tx_id: 1,
             transaction_type: TransactionType::Deposit,
             amount: Some(dec!(12.3456)),
+            counterparty: None,
         };
 
-        let account = Account::new(1, tx_event.clone());
+        let account = Account::new(1, tx_event.clone(), &AccountConfig::default());
         let mut expected = Account {
             balance: Balance {
                 available: dec!(12.3456),
@@ -307,10 +1051,423 @@ mod tests {
             },
             transactions: HashMap::new(),
             disputed_transactions: HashSet::new(),
+            charged_back_transactions: HashSet::new(),
+            adjustments: Decimal::default(),
             previous_tx_id: 1,
             locked: false,
+            recent: VecDeque::new(),
+            flags: Vec::new(),
+            book: Book::default(),
         };
         expected.record_tx(1, tx_event);
         assert_eq!(account, expected);
     }
+
+    #[test]
+    fn test_dispute_policy() {
+        let deposit = TransactionEvent {
+            client_id: 1,
+            tx_id: 1,
+            transaction_type: TransactionType::Deposit,
+            amount: Some(dec!(10)),
+            counterparty: None,
+        };
+        let withdrawal = TransactionEvent {
+            client_id: 1,
+            tx_id: 2,
+            transaction_type: TransactionType::Withdrawal,
+            amount: Some(dec!(4)),
+            counterparty: None,
+        };
+        let dispute = TransactionEvent {
+            client_id: 1,
+            tx_id: 1,
+            transaction_type: TransactionType::Dispute,
+            amount: None,
+            counterparty: None,
+        };
+
+        let strict = AccountConfig::default();
+        let mut account = Account::new(1, deposit.clone(), &strict);
+        account.apply_tx(2, withdrawal.clone(), &strict).unwrap();
+        assert!(matches!(
+            account.apply_tx(1, dispute.clone(), &strict),
+            Err(LedgerError::InsufficientFunds { .. })
+        ));
+
+        let relaxed = AccountConfig {
+            allow_negative_available: true,
+            ..AccountConfig::default()
+        };
+        let mut account = Account::new(1, deposit, &relaxed);
+        account.apply_tx(2, withdrawal, &relaxed).unwrap();
+        account.apply_tx(1, dispute, &relaxed).unwrap();
+        assert_eq!(account.balance.available, dec!(-4));
+        assert_eq!(account.balance.held, dec!(10));
+    }
+
+    #[test]
+    fn test_withdrawal_limits() {
+        let config = AccountConfig {
+            limits: [ClientLimits {
+                client_id: 1,
+                max_withdrawal: Some(dec!(5)),
+                withdrawal_cap: Some(dec!(8)),
+                cap_window: Some(3),
+                overdraft: Some(dec!(2)),
+            }]
+            .into_iter()
+            .collect(),
+            ..AccountConfig::default()
+        };
+        let event = |transaction_type, tx_id, amount| TransactionEvent {
+            client_id: 1,
+            tx_id,
+            transaction_type,
+            amount: Some(amount),
+            counterparty: None,
+        };
+        let mut account = Account::new(1, event(TransactionType::Deposit, 1, dec!(4)), &config);
+        assert!(matches!(
+            account.apply_tx(2, event(TransactionType::Withdrawal, 2, dec!(6)), &config),
+            Err(LedgerError::WithdrawalLimitExceeded { .. })
+        ));
+        account
+            .apply_tx(3, event(TransactionType::Withdrawal, 3, dec!(5)), &config)
+            .unwrap();
+        assert_eq!(account.balance.available, dec!(-1));
+        assert!(matches!(
+            account.apply_tx(4, event(TransactionType::Withdrawal, 4, dec!(2)), &config),
+            Err(LedgerError::OverdraftExceeded { .. })
+        ));
+        account
+            .apply_tx(5, event(TransactionType::Deposit, 5, dec!(10)), &config)
+            .unwrap();
+        assert!(matches!(
+            account.apply_tx(6, event(TransactionType::Withdrawal, 6, dec!(4)), &config),
+            Err(LedgerError::WithdrawalCapExceeded { .. })
+        ));
+        account
+            .apply_tx(7, event(TransactionType::Withdrawal, 7, dec!(3)), &config)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_rule_freeze() {
+        let config = AccountConfig {
+            rules: serde_json::from_str(
+                r#"{"rules": [{"name": "velocity", "kind": "velocity", "type": "withdrawal", "max": 1, "window": 2, "action": "freeze"}]}"#,
+            )
+            .unwrap(),
+            ..AccountConfig::default()
+        };
+        let event = |transaction_type, tx_id| TransactionEvent {
+            client_id: 1,
+            tx_id,
+            transaction_type,
+            amount: Some(dec!(1)),
+            counterparty: None,
+        };
+        let deposit = TransactionEvent {
+            amount: Some(dec!(2)),
+            ..event(TransactionType::Deposit, 1)
+        };
+        let mut account = Account::new(1, deposit, &config);
+        account
+            .apply_tx(2, event(TransactionType::Withdrawal, 2), &config)
+            .unwrap();
+        // A duplicate would match the rule, but is refused before rules are evaluated.
+        assert!(matches!(
+            account.apply_tx(2, event(TransactionType::Withdrawal, 2), &config),
+            Err(LedgerError::SuspiciousTransaction(2))
+        ));
+        assert!(!account.locked);
+        assert!(matches!(
+            account.apply_tx(3, event(TransactionType::Withdrawal, 3), &config),
+            Err(LedgerError::AccountFrozen { .. })
+        ));
+        assert!(account.locked);
+        assert_eq!(account.balance.available, dec!(1));
+    }
+
+    #[tokio::test]
+    async fn test_store_errors() {
+        let ledger = InMemoryLedger::<Account>::new();
+        let deposit = TransactionEvent {
+            client_id: 1,
+            tx_id: 1,
+            transaction_type: TransactionType::Deposit,
+            amount: Some(dec!(10)),
+            counterparty: None,
+        };
+        let withdrawal = TransactionEvent {
+            client_id: 1,
+            tx_id: 2,
+            transaction_type: TransactionType::Withdrawal,
+            amount: Some(dec!(20)),
+            counterparty: None,
+        };
+
+        Arc::clone(&ledger)
+            .process_transaction(1, 1, deposit)
+            .await
+            .unwrap();
+        assert!(matches!(
+            Arc::clone(&ledger)
+                .process_transaction(1, 2, withdrawal)
+                .await,
+            Err(StoreError::Rejected(LedgerError::InsufficientFunds { .. }))
+        ));
+        assert!(matches!(
+            Arc::clone(&ledger).snapshot(2).await,
+            Err(StoreError::NotFound(2))
+        ));
+
+        // A refused first transaction is reported, and the account is created without it.
+        let empty = TransactionEvent {
+            client_id: 2,
+            tx_id: 3,
+            transaction_type: TransactionType::Deposit,
+            amount: None,
+            counterparty: None,
+        };
+        assert!(matches!(
+            Arc::clone(&ledger).process_transaction(2, 3, empty).await,
+            Err(StoreError::Rejected(LedgerError::MissingAmount(3)))
+        ));
+        let snapshot = Arc::clone(&ledger).snapshot(2).await.unwrap();
+        assert_eq!(snapshot.total, dec!(0));
+        assert!(Arc::clone(&ledger)
+            .transactions(2)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_transfer() {
+        let ledger = InMemoryLedger::<Account>::new();
+        let event = |transaction_type, client_id, tx_id, amount, counterparty| TransactionEvent {
+            client_id,
+            tx_id,
+            transaction_type,
+            amount,
+            counterparty,
+        };
+        let apply = |event: TransactionEvent| {
+            Arc::clone(&ledger).process_transaction(event.client_id, event.tx_id, event)
+        };
+        let total = |client| {
+            let snapshot = Arc::clone(&ledger).snapshot(client);
+            async move { snapshot.await.unwrap().total }
+        };
+
+        apply(event(TransactionType::Deposit, 1, 1, Some(dec!(10)), None))
+            .await
+            .unwrap();
+        apply(event(TransactionType::Deposit, 2, 2, Some(dec!(1)), None))
+            .await
+            .unwrap();
+        apply(event(
+            TransactionType::Transfer,
+            1,
+            3,
+            Some(dec!(4)),
+            Some(2),
+        ))
+        .await
+        .unwrap();
+        assert_eq!((total(1).await, total(2).await), (dec!(6), dec!(5)));
+
+        // Only the receiving client can dispute a transfer, which then behaves like a deposit.
+        assert!(matches!(
+            apply(event(TransactionType::Dispute, 1, 3, None, None)).await,
+            Err(StoreError::Rejected(LedgerError::OutgoingTransferDispute(
+                3
+            )))
+        ));
+        apply(event(TransactionType::Dispute, 2, 3, None, None))
+            .await
+            .unwrap();
+        apply(event(TransactionType::Chargeback, 2, 3, None, None))
+            .await
+            .unwrap();
+        assert_eq!(total(2).await, dec!(1));
+
+        // Neither side changes when either of them refuses the transfer.
+        assert!(matches!(
+            apply(event(
+                TransactionType::Transfer,
+                1,
+                4,
+                Some(dec!(1)),
+                Some(2)
+            ))
+            .await,
+            Err(StoreError::Rejected(LedgerError::LockedAccount(4)))
+        ));
+        assert!(matches!(
+            apply(event(
+                TransactionType::Transfer,
+                1,
+                5,
+                Some(dec!(1)),
+                Some(9)
+            ))
+            .await,
+            Err(StoreError::Rejected(LedgerError::UnknownCounterparty {
+                tx_id: 5,
+                client: 9
+            }))
+        ));
+        assert!(matches!(
+            apply(event(
+                TransactionType::Transfer,
+                1,
+                6,
+                Some(dec!(1)),
+                Some(1)
+            ))
+            .await,
+            Err(StoreError::Rejected(LedgerError::InvalidCounterparty(6)))
+        ));
+        assert_eq!((total(1).await, total(2).await), (dec!(6), dec!(1)));
+    }
+
+    #[tokio::test]
+    async fn test_fees() {
+        let config = AccountConfig {
+            fees: [
+                Fee {
+                    transaction_type: TransactionType::Deposit,
+                    flat: Some(dec!(0.5)),
+                    percent: None,
+                },
+                Fee {
+                    transaction_type: TransactionType::Withdrawal,
+                    flat: Some(dec!(1)),
+                    percent: Some(dec!(10)),
+                },
+            ]
+            .into_iter()
+            .collect(),
+            house: Some(0),
+            ..AccountConfig::default()
+        };
+        let ledger = InMemoryLedger::<Account>::with_config(config);
+        let event = |transaction_type, client_id, tx_id, amount| TransactionEvent {
+            client_id,
+            tx_id,
+            transaction_type,
+            amount: Some(amount),
+            counterparty: None,
+        };
+        let apply = |event: TransactionEvent| {
+            Arc::clone(&ledger).process_transaction(event.client_id, event.tx_id, event)
+        };
+        let available = |client| {
+            let snapshot = Arc::clone(&ledger).snapshot(client);
+            async move { snapshot.await.unwrap().available }
+        };
+
+        // The house account opens with the first fee it collects.
+        apply(event(TransactionType::Deposit, 1, 1, dec!(100)))
+            .await
+            .unwrap();
+        assert_eq!(
+            (available(1).await, available(0).await),
+            (dec!(99.5), dec!(0.5))
+        );
+        apply(event(TransactionType::Withdrawal, 1, 2, dec!(20)))
+            .await
+            .unwrap();
+        assert_eq!(
+            (available(1).await, available(0).await),
+            (dec!(76.5), dec!(3.5))
+        );
+        apply(event(TransactionType::Fee, 1, 3, dec!(5)))
+            .await
+            .unwrap();
+        apply(event(TransactionType::Interest, 1, 4, dec!(2)))
+            .await
+            .unwrap();
+        assert_eq!(
+            (available(1).await, available(0).await),
+            (dec!(73.5), dec!(6.5))
+        );
+
+        // The fee has to be covered too, and neither side changes when it is not.
+        assert!(matches!(
+            apply(event(TransactionType::Withdrawal, 1, 5, dec!(70))).await,
+            Err(StoreError::Rejected(LedgerError::InsufficientFunds { .. }))
+        ));
+        assert!(matches!(
+            apply(event(TransactionType::Deposit, 1, 6, dec!(0.2))).await,
+            Err(StoreError::Rejected(LedgerError::FeeExceedsAmount { .. }))
+        ));
+        // The house cannot charge itself fees or pay itself interest.
+        for transaction_type in [TransactionType::Fee, TransactionType::Interest] {
+            assert!(matches!(
+                apply(event(transaction_type, 0, 7, dec!(1))).await,
+                Err(StoreError::Rejected(LedgerError::HousePosting(7)))
+            ));
+        }
+        assert_eq!(
+            (available(1).await, available(0).await),
+            (dec!(73.5), dec!(6.5))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_double_entry() {
+        let config = AccountConfig {
+            fees: [Fee {
+                transaction_type: TransactionType::Withdrawal,
+                flat: Some(dec!(1)),
+                percent: None,
+            }]
+            .into_iter()
+            .collect(),
+            house: Some(0),
+            double_entry: true,
+            ..AccountConfig::default()
+        };
+        let ledger = InMemoryLedger::<Account>::with_config(config);
+        let event = |transaction_type, client_id, tx_id, amount, counterparty| TransactionEvent {
+            client_id,
+            tx_id,
+            transaction_type,
+            amount,
+            counterparty,
+        };
+        for event in [
+            event(TransactionType::Deposit, 1, 1, Some(dec!(10)), None),
+            event(TransactionType::Deposit, 2, 2, Some(dec!(5)), None),
+            event(TransactionType::Transfer, 1, 3, Some(dec!(4)), Some(2)),
+            event(TransactionType::Withdrawal, 1, 4, Some(dec!(2)), None),
+            event(TransactionType::Dispute, 2, 3, None, None),
+            event(TransactionType::Chargeback, 2, 3, None, None),
+            event(TransactionType::Interest, 1, 5, Some(dec!(0.5)), None),
+        ] {
+            Arc::clone(&ledger)
+                .process_transaction(event.client_id, event.tx_id, event)
+                .await
+                .unwrap();
+        }
+
+        let accounts = Arc::clone(&ledger).aggregates().await.unwrap();
+        let trial_balance = accounts
+            .iter()
+            .map(|(_, account)| account.book())
+            .collect::<TrialBalance>();
+        assert!(trial_balance.is_balanced());
+        assert!(invariants::check(accounts.iter().map(|(id, account)| (*id, account))).is_empty());
+        assert_eq!(
+            trial_balance.balances[&LedgerAccount::ChargebackLoss],
+            dec!(-4)
+        );
+        assert!(
+            invariants::check_books(accounts.iter().map(|(id, account)| (*id, account))).is_empty()
+        );
+    }
 }
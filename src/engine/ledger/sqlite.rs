@@ -0,0 +1,708 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    io,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+use futures::future::BoxFuture;
+use rusqlite::{params, Connection, Row};
+use rust_decimal::Decimal;
+
+use super::{
+    Account, Aggregate, Aggregates, Applied, Counterparty, Ledger, LedgerConfig, LedgerResult,
+};
+use crate::engine::{
+    bookkeeping::Book,
+    config::AccountConfig,
+    domain::{AccountSnapshot, Balance, TransactionEvent, TransactionType},
+    error::{LedgerError, StoreError},
+};
+
+/// Amounts are stored as text so that they round-trip without losing precision. `recent`
+/// keeps the latest transactions of each account that rules and withdrawal caps look back on.
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS accounts (
+    client      INTEGER PRIMARY KEY,
+    available   TEXT NOT NULL,
+    held        TEXT NOT NULL,
+    locked      INTEGER NOT NULL,
+    previous_tx INTEGER NOT NULL,
+    adjustments TEXT NOT NULL DEFAULT '0'
+);
+CREATE TABLE IF NOT EXISTS transactions (
+    client       INTEGER NOT NULL REFERENCES accounts (client),
+    tx           INTEGER NOT NULL,
+    type         TEXT NOT NULL,
+    amount       TEXT,
+    counterparty INTEGER,
+    PRIMARY KEY (client, tx)
+);
+CREATE TABLE IF NOT EXISTS disputes (
+    client INTEGER NOT NULL,
+    tx     INTEGER NOT NULL,
+    PRIMARY KEY (client, tx),
+    FOREIGN KEY (client, tx) REFERENCES transactions (client, tx)
+);
+CREATE TABLE IF NOT EXISTS recent (
+    client       INTEGER NOT NULL REFERENCES accounts (client),
+    position     INTEGER NOT NULL,
+    tx           INTEGER NOT NULL,
+    type         TEXT NOT NULL,
+    amount       TEXT,
+    counterparty INTEGER,
+    PRIMARY KEY (client, position)
+);
+CREATE TABLE IF NOT EXISTS chargebacks (
+    client INTEGER NOT NULL,
+    tx     INTEGER NOT NULL,
+    PRIMARY KEY (client, tx),
+    FOREIGN KEY (client, tx) REFERENCES transactions (client, tx)
+);
+";
+
+type Error = StoreError<u16, LedgerError>;
+
+struct State {
+    connection: Connection,
+    /// Write-through cache of the `accounts`, `transactions` and `disputes` tables.
+    accounts: HashMap<u16, Account>,
+}
+
+/// A [`Ledger`] that keeps the accounts, their transactions and disputes in a SQLite file.
+///
+/// Every applied transaction is committed in its own SQL transaction, so the file can be
+/// inspected with standard SQL tooling while the ledger runs, and reopened after a restart.
+pub struct SqliteLedger {
+    state: Mutex<State>,
+    config: AccountConfig,
+}
+
+impl SqliteLedger {
+    /// Opens (or creates) the database at `path`, loading the accounts it already holds.
+    pub async fn open<P>(path: P, config: AccountConfig) -> Result<Arc<Self>, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let path = PathBuf::from(path.as_ref());
+        tokio::task::spawn_blocking(move || {
+            let connection = Connection::open(path).map_err(storage)?;
+            connection.execute_batch(SCHEMA).map_err(storage)?;
+            migrate(&connection).map_err(storage)?;
+            let accounts = load_accounts(&connection).map_err(storage)?;
+            Ok(Arc::new(Self {
+                state: Mutex::new(State {
+                    connection,
+                    accounts,
+                }),
+                config,
+            }))
+        })
+        .await
+        .map_err(|_| StoreError::Closed)?
+    }
+
+    fn apply(
+        &self,
+        id: u16,
+        tx_id: u32,
+        transaction: TransactionEvent,
+    ) -> Result<Applied<u16>, Error> {
+        let mut state = self.state.lock().map_err(|_| StoreError::Closed)?;
+        let State {
+            connection,
+            accounts,
+        } = &mut *state;
+        let counterparties = Account::counterparties(&transaction, &self.config);
+        let result = self.config.apply(accounts, id, tx_id, transaction);
+        // A refused transaction may still have created or locked the account, which is
+        // stored too.
+        let changed = match result {
+            Ok(_) => {
+                let mut changed = vec![id];
+                changed.extend(counterparties);
+                changed
+            }
+            Err(StoreError::Rejected(_)) if accounts.contains_key(&id) => vec![id],
+            Err(error) => return Err(error),
+        };
+        if let Err(err) = persist(connection, tx_id, accounts, &changed) {
+            // The database is the source of truth, drop whatever was not committed.
+            for id in changed {
+                match load_account(connection, id) {
+                    Ok(Some(account)) => accounts.insert(id, account),
+                    _ => accounts.remove(&id),
+                };
+            }
+            return Err(storage(err));
+        }
+        Ok(Applied { id, flags: result? })
+    }
+
+    /// Runs `read` on the accounts on the blocking thread pool, as the lock may be held
+    /// while a transaction is committed to the database.
+    async fn read<T, F>(self: Arc<Self>, read: F) -> Result<T, Error>
+    where
+        F: FnOnce(&HashMap<u16, Account>) -> Result<T, Error> + Send + 'static,
+        T: Send + 'static,
+    {
+        tokio::task::spawn_blocking(move || {
+            let state = self.state.lock().map_err(|_| StoreError::Closed)?;
+            read(&state.accounts)
+        })
+        .await
+        .map_err(|_| StoreError::Closed)?
+    }
+}
+
+impl Ledger<Account> for SqliteLedger {
+    fn process_transaction(
+        self: Arc<Self>,
+        id: u16,
+        tx_id: u32,
+        transaction: TransactionEvent,
+    ) -> BoxFuture<'static, LedgerResult<Account, Applied<u16>>> {
+        Box::pin(async move {
+            tokio::task::spawn_blocking(move || self.apply(id, tx_id, transaction))
+                .await
+                .map_err(|_| StoreError::Closed)?
+        })
+    }
+
+    fn snapshot(
+        self: Arc<Self>,
+        id: u16,
+    ) -> BoxFuture<'static, LedgerResult<Account, AccountSnapshot>> {
+        Box::pin(self.read(move |accounts| match accounts.get(&id) {
+            Some(account) => Ok(account.snapshot(id)),
+            None => Err(StoreError::NotFound(id)),
+        }))
+    }
+
+    fn all_snapshots(
+        self: Arc<Self>,
+    ) -> BoxFuture<'static, LedgerResult<Account, Vec<AccountSnapshot>>> {
+        Box::pin(self.read(|accounts| {
+            let mut entries = accounts.iter().collect::<Vec<_>>();
+            entries.sort_by_key(|(id, _)| *id);
+            Ok(entries
+                .into_iter()
+                .map(|(id, account)| account.snapshot(*id))
+                .collect())
+        }))
+    }
+
+    fn transactions(
+        self: Arc<Self>,
+        id: u16,
+    ) -> BoxFuture<'static, LedgerResult<Account, Vec<TransactionEvent>>> {
+        Box::pin(self.read(move |accounts| match accounts.get(&id) {
+            Some(account) => Ok(account.transactions()),
+            None => Err(StoreError::NotFound(id)),
+        }))
+    }
+
+    fn aggregates(
+        self: Arc<Self>,
+    ) -> BoxFuture<'static, LedgerResult<Account, Aggregates<Account>>> {
+        Box::pin(self.read(|accounts| {
+            let mut accounts = accounts
+                .iter()
+                .map(|(id, account)| (*id, account.clone()))
+                .collect::<Vec<_>>();
+            accounts.sort_by_key(|(id, _)| *id);
+            Ok(accounts)
+        }))
+    }
+}
+
+/// Adds the columns that databases created by earlier versions lack.
+fn migrate(connection: &Connection) -> rusqlite::Result<()> {
+    add_column(
+        connection,
+        "accounts",
+        "adjustments",
+        "TEXT NOT NULL DEFAULT '0'",
+    )?;
+    add_column(connection, "transactions", "counterparty", "INTEGER")
+}
+
+fn add_column(
+    connection: &Connection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> rusqlite::Result<()> {
+    let exists: bool = connection.query_row(
+        &format!("SELECT COUNT(*) > 0 FROM pragma_table_info('{table}') WHERE name = ?1"),
+        params![column],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        connection.execute(
+            &format!("ALTER TABLE {table} ADD COLUMN {column} {definition}"),
+            [],
+        )?;
+    }
+    Ok(())
+}
+
+fn storage(err: rusqlite::Error) -> Error {
+    StoreError::Storage(Box::new(err))
+}
+
+/// Writes the state `tx_id` left the accounts `ids` in, in a single database transaction.
+fn persist(
+    connection: &mut Connection,
+    tx_id: u32,
+    accounts: &HashMap<u16, Account>,
+    ids: &[u16],
+) -> rusqlite::Result<()> {
+    let db = connection.transaction()?;
+    for &id in ids {
+        persist_account(&db, id, tx_id, &accounts[&id])?;
+    }
+    db.commit()
+}
+
+fn persist_account(
+    db: &rusqlite::Transaction,
+    id: u16,
+    tx_id: u32,
+    account: &Account,
+) -> rusqlite::Result<()> {
+    db.execute(
+        "INSERT INTO accounts (client, available, held, locked, previous_tx, adjustments)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+         ON CONFLICT (client) DO UPDATE SET
+             available = excluded.available,
+             held = excluded.held,
+             locked = excluded.locked,
+             previous_tx = excluded.previous_tx,
+             adjustments = excluded.adjustments",
+        params![
+            id,
+            account.balance.available.to_string(),
+            account.balance.held.to_string(),
+            account.locked,
+            account.previous_tx_id,
+            account.adjustments.to_string(),
+        ],
+    )?;
+    if let Some(event) = account.transactions.get(&tx_id) {
+        db.execute(
+            "INSERT OR REPLACE INTO transactions (client, tx, type, amount, counterparty)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                id,
+                tx_id,
+                type_name(&event.transaction_type),
+                event.amount.map(|amount| amount.to_string()),
+                event.counterparty,
+            ],
+        )?;
+    }
+    if account.disputed_transactions.contains(&tx_id) {
+        db.execute(
+            "INSERT OR IGNORE INTO disputes (client, tx) VALUES (?1, ?2)",
+            params![id, tx_id],
+        )?;
+    } else {
+        db.execute(
+            "DELETE FROM disputes WHERE client = ?1 AND tx = ?2",
+            params![id, tx_id],
+        )?;
+    }
+    // Only the account that applied the transaction remembers it.
+    if account.recent.back().map(|event| event.tx_id) == Some(tx_id) {
+        db.execute("DELETE FROM recent WHERE client = ?1", params![id])?;
+        for (position, event) in account.recent.iter().enumerate() {
+            db.execute(
+                "INSERT INTO recent (client, position, tx, type, amount, counterparty)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    id,
+                    position,
+                    event.tx_id,
+                    type_name(&event.transaction_type),
+                    event.amount.map(|amount| amount.to_string()),
+                    event.counterparty,
+                ],
+            )?;
+        }
+    }
+    if account.charged_back_transactions.contains(&tx_id) {
+        db.execute(
+            "INSERT OR IGNORE INTO chargebacks (client, tx) VALUES (?1, ?2)",
+            params![id, tx_id],
+        )?;
+    }
+    Ok(())
+}
+
+fn load_accounts(connection: &Connection) -> rusqlite::Result<HashMap<u16, Account>> {
+    let mut accounts = HashMap::new();
+    let mut statement = connection.prepare("SELECT client FROM accounts")?;
+    let ids = statement
+        .query_map([], |row| row.get::<_, u16>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    for id in ids {
+        if let Some(account) = load_account(connection, id)? {
+            accounts.insert(id, account);
+        }
+    }
+    Ok(accounts)
+}
+
+fn load_account(connection: &Connection, id: u16) -> rusqlite::Result<Option<Account>> {
+    let mut statement = connection.prepare(
+        "SELECT available, held, locked, previous_tx, adjustments FROM accounts WHERE client = ?1",
+    )?;
+    let mut rows = statement.query(params![id])?;
+    let row = match rows.next()? {
+        Some(row) => row,
+        None => return Ok(None),
+    };
+    let mut account = Account {
+        balance: Balance {
+            available: decimal(row, 0)?,
+            held: decimal(row, 1)?,
+        },
+        transactions: HashMap::new(),
+        disputed_transactions: HashSet::new(),
+        charged_back_transactions: HashSet::new(),
+        adjustments: decimal(row, 4)?,
+        previous_tx_id: row.get(3)?,
+        locked: row.get(2)?,
+        recent: VecDeque::new(),
+        flags: Vec::new(),
+        book: Book::default(),
+    };
+    account.open_books(id);
+
+    let mut statement = connection
+        .prepare("SELECT tx, type, amount, counterparty FROM transactions WHERE client = ?1")?;
+    let mut rows = statement.query(params![id])?;
+    while let Some(row) = rows.next()? {
+        let tx_id = row.get(0)?;
+        let event = TransactionEvent {
+            client_id: id,
+            tx_id,
+            transaction_type: parse_type(row, 1)?,
+            amount: optional_decimal(row, 2)?,
+            counterparty: row.get(3)?,
+        };
+        account.transactions.insert(tx_id, event);
+    }
+
+    let mut statement = connection.prepare(
+        "SELECT tx, type, amount, counterparty FROM recent WHERE client = ?1 ORDER BY position",
+    )?;
+    let mut rows = statement.query(params![id])?;
+    while let Some(row) = rows.next()? {
+        account.recent.push_back(TransactionEvent {
+            client_id: id,
+            tx_id: row.get(0)?,
+            transaction_type: parse_type(row, 1)?,
+            amount: optional_decimal(row, 2)?,
+            counterparty: row.get(3)?,
+        });
+    }
+
+    let mut statement = connection.prepare("SELECT tx FROM disputes WHERE client = ?1")?;
+    for tx_id in statement.query_map(params![id], |row| row.get(0))? {
+        account.disputed_transactions.insert(tx_id?);
+    }
+    let mut statement = connection.prepare("SELECT tx FROM chargebacks WHERE client = ?1")?;
+    for tx_id in statement.query_map(params![id], |row| row.get(0))? {
+        account.charged_back_transactions.insert(tx_id?);
+    }
+    Ok(Some(account))
+}
+
+fn decimal(row: &Row, index: usize) -> rusqlite::Result<Decimal> {
+    let text = row.get::<_, String>(index)?;
+    Decimal::from_str(&text).map_err(|err| {
+        rusqlite::Error::FromSqlConversionFailure(index, rusqlite::types::Type::Text, Box::new(err))
+    })
+}
+
+fn optional_decimal(row: &Row, index: usize) -> rusqlite::Result<Option<Decimal>> {
+    match row.get::<_, Option<String>>(index)? {
+        Some(_) => decimal(row, index).map(Some),
+        None => Ok(None),
+    }
+}
+
+fn type_name(transaction_type: &TransactionType) -> &'static str {
+    match transaction_type {
+        TransactionType::Deposit => "deposit",
+        TransactionType::Withdrawal => "withdrawal",
+        TransactionType::Dispute => "dispute",
+        TransactionType::Resolve => "resolve",
+        TransactionType::Chargeback => "chargeback",
+        TransactionType::Transfer => "transfer",
+        TransactionType::Fee => "fee",
+        TransactionType::Interest => "interest",
+    }
+}
+
+fn parse_type(row: &Row, index: usize) -> rusqlite::Result<TransactionType> {
+    let text = row.get::<_, String>(index)?;
+    Ok(match text.as_str() {
+        "deposit" => TransactionType::Deposit,
+        "withdrawal" => TransactionType::Withdrawal,
+        "dispute" => TransactionType::Dispute,
+        "resolve" => TransactionType::Resolve,
+        "chargeback" => TransactionType::Chargeback,
+        "transfer" => TransactionType::Transfer,
+        "fee" => TransactionType::Fee,
+        "interest" => TransactionType::Interest,
+        _ => {
+            let err = io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unknown transaction type `{text}`"),
+            );
+            return Err(rusqlite::Error::FromSqlConversionFailure(
+                index,
+                rusqlite::types::Type::Text,
+                Box::new(err),
+            ));
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use rust_decimal_macros::dec;
+
+    use super::*;
+    use crate::engine::{fees::Fee, invariants, limits::ClientLimits};
+
+    #[tokio::test]
+    async fn test_reopen() {
+        let path = std::env::temp_dir().join(format!("leviathan-ledger-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let event = |transaction_type, tx_id, amount| TransactionEvent {
+            client_id: 1,
+            tx_id,
+            transaction_type,
+            amount,
+            counterparty: None,
+        };
+
+        let ledger = SqliteLedger::open(&path, AccountConfig::default())
+            .await
+            .unwrap();
+        for event in [
+            event(TransactionType::Deposit, 1, Some(dec!(10.5))),
+            event(TransactionType::Deposit, 2, Some(dec!(4))),
+            event(TransactionType::Dispute, 2, None),
+        ] {
+            Arc::clone(&ledger)
+                .process_transaction(1, event.tx_id, event)
+                .await
+                .unwrap();
+        }
+        assert!(matches!(
+            Arc::clone(&ledger)
+                .process_transaction(1, 3, event(TransactionType::Withdrawal, 3, Some(dec!(11))))
+                .await,
+            Err(StoreError::Rejected(LedgerError::InsufficientFunds { .. }))
+        ));
+        // A refused first transaction still creates the account, as in memory.
+        let withdrawal = TransactionEvent {
+            client_id: 2,
+            ..event(TransactionType::Withdrawal, 4, Some(dec!(1)))
+        };
+        assert!(matches!(
+            Arc::clone(&ledger)
+                .process_transaction(2, 4, withdrawal)
+                .await,
+            Err(StoreError::Rejected(LedgerError::InsufficientFunds { .. }))
+        ));
+        let before = Arc::clone(&ledger).snapshot(1).await.unwrap();
+        drop(ledger);
+
+        // The dispute survives the restart, so it can still be resolved.
+        let ledger = SqliteLedger::open(&path, AccountConfig::default())
+            .await
+            .unwrap();
+        assert_eq!(Arc::clone(&ledger).snapshot(1).await.unwrap(), before);
+        assert_eq!(
+            Arc::clone(&ledger).snapshot(2).await.unwrap().total,
+            dec!(0)
+        );
+        Arc::clone(&ledger)
+            .process_transaction(1, 2, event(TransactionType::Resolve, 2, None))
+            .await
+            .unwrap();
+        let after = Arc::clone(&ledger).snapshot(1).await.unwrap();
+        assert_eq!(after.available, dec!(14.5));
+        assert_eq!(after.held, dec!(0));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_reopen_withdrawal_cap() {
+        let path = std::env::temp_dir().join(format!("leviathan-cap-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let config = AccountConfig {
+            limits: [ClientLimits {
+                client_id: 1,
+                max_withdrawal: None,
+                withdrawal_cap: Some(dec!(8)),
+                cap_window: Some(3),
+                overdraft: None,
+            }]
+            .into_iter()
+            .collect(),
+            ..AccountConfig::default()
+        };
+        let event = |transaction_type, tx_id, amount| TransactionEvent {
+            client_id: 1,
+            tx_id,
+            transaction_type,
+            amount: Some(amount),
+            counterparty: None,
+        };
+
+        let ledger = SqliteLedger::open(&path, config.clone()).await.unwrap();
+        for event in [
+            event(TransactionType::Deposit, 1, dec!(20)),
+            event(TransactionType::Withdrawal, 2, dec!(5)),
+        ] {
+            Arc::clone(&ledger)
+                .process_transaction(1, event.tx_id, event)
+                .await
+                .unwrap();
+        }
+        drop(ledger);
+
+        // The withdrawal before the restart still counts towards the cap.
+        let ledger = SqliteLedger::open(&path, config).await.unwrap();
+        assert!(matches!(
+            Arc::clone(&ledger)
+                .process_transaction(1, 3, event(TransactionType::Withdrawal, 3, dec!(4)))
+                .await,
+            Err(StoreError::Rejected(
+                LedgerError::WithdrawalCapExceeded { .. }
+            ))
+        ));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_transfer() {
+        let path =
+            std::env::temp_dir().join(format!("leviathan-transfer-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let event = |transaction_type, client_id, tx_id, amount, counterparty| TransactionEvent {
+            client_id,
+            tx_id,
+            transaction_type,
+            amount,
+            counterparty,
+        };
+
+        let ledger = SqliteLedger::open(&path, AccountConfig::default())
+            .await
+            .unwrap();
+        for event in [
+            event(TransactionType::Deposit, 1, 1, Some(dec!(10)), None),
+            event(TransactionType::Deposit, 2, 2, Some(dec!(1)), None),
+            event(TransactionType::Transfer, 1, 3, Some(dec!(4)), Some(2)),
+        ] {
+            Arc::clone(&ledger)
+                .process_transaction(event.client_id, event.tx_id, event)
+                .await
+                .unwrap();
+        }
+        assert!(matches!(
+            Arc::clone(&ledger)
+                .process_transaction(
+                    1,
+                    4,
+                    event(TransactionType::Transfer, 1, 4, Some(dec!(1)), Some(3))
+                )
+                .await,
+            Err(StoreError::Rejected(LedgerError::UnknownCounterparty {
+                tx_id: 4,
+                client: 3
+            }))
+        ));
+        let sent = Arc::clone(&ledger).transactions(1).await.unwrap();
+        drop(ledger);
+
+        // The transfer reads back with its receiver.
+        let ledger = SqliteLedger::open(&path, AccountConfig::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            Arc::clone(&ledger).snapshot(1).await.unwrap().total,
+            dec!(6)
+        );
+        assert_eq!(Arc::clone(&ledger).transactions(1).await.unwrap(), sent);
+        assert_eq!(sent[1].counterparty, Some(2));
+        Arc::clone(&ledger)
+            .process_transaction(2, 3, event(TransactionType::Dispute, 2, 3, None, None))
+            .await
+            .unwrap();
+        let receiver = Arc::clone(&ledger).snapshot(2).await.unwrap();
+        assert_eq!((receiver.available, receiver.held), (dec!(1), dec!(4)));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_fees() {
+        let path = std::env::temp_dir().join(format!("leviathan-fees-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let config = AccountConfig {
+            fees: [Fee {
+                transaction_type: TransactionType::Withdrawal,
+                flat: Some(dec!(1)),
+                percent: None,
+            }]
+            .into_iter()
+            .collect(),
+            house: Some(0),
+            ..AccountConfig::default()
+        };
+        let event = |transaction_type, tx_id, amount| TransactionEvent {
+            client_id: 1,
+            tx_id,
+            transaction_type,
+            amount: Some(amount),
+            counterparty: None,
+        };
+
+        let ledger = SqliteLedger::open(&path, config.clone()).await.unwrap();
+        for event in [
+            event(TransactionType::Deposit, 1, dec!(10)),
+            event(TransactionType::Withdrawal, 2, dec!(4)),
+            event(TransactionType::Interest, 3, dec!(0.25)),
+        ] {
+            Arc::clone(&ledger)
+                .process_transaction(1, event.tx_id, event)
+                .await
+                .unwrap();
+        }
+        drop(ledger);
+
+        let ledger = SqliteLedger::open(&path, config).await.unwrap();
+        let snapshots = Arc::clone(&ledger).all_snapshots().await.unwrap();
+        let available = snapshots
+            .iter()
+            .map(|snapshot| (snapshot.client_id, snapshot.available))
+            .collect::<Vec<_>>();
+        assert_eq!(available, vec![(0, dec!(0.75)), (1, dec!(5.25))]);
+        let accounts = Arc::clone(&ledger).aggregates().await.unwrap();
+        assert!(invariants::check(accounts.iter().map(|(id, account)| (*id, account))).is_empty());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,125 @@
+use std::{collections::HashMap, io, path::Path};
+
+use rust_decimal::Decimal;
+use serde::Deserialize;
+
+/// Withdrawal limits of a single client. Every limit is optional.
+#[derive(Debug, Default, Deserialize, Clone, PartialEq)]
+pub struct ClientLimits {
+    /// Client ID
+    #[serde(rename = "client")]
+    pub client_id: u16,
+    /// The largest amount a single withdrawal may take.
+    #[serde(default)]
+    pub max_withdrawal: Option<Decimal>,
+    /// The largest amount the withdrawals among the last `cap_window` transactions of the
+    /// client may take together, counting the checked one.
+    #[serde(default)]
+    pub withdrawal_cap: Option<Decimal>,
+    #[serde(default)]
+    pub cap_window: Option<usize>,
+    /// How far withdrawals may take the available balance below zero.
+    #[serde(default)]
+    pub overdraft: Option<Decimal>,
+}
+
+impl ClientLimits {
+    /// The rolling withdrawal cap and the number of transactions it applies to, if set.
+    pub fn rolling_cap(&self) -> Option<(Decimal, usize)> {
+        Some((self.withdrawal_cap?, self.cap_window?))
+    }
+}
+
+/// The withdrawal limits of every client that has any.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Limits {
+    clients: HashMap<u16, ClientLimits>,
+}
+
+impl Limits {
+    /// Reads the limits from a CSV file with a `client` column and any of the
+    /// `max_withdrawal`, `withdrawal_cap`, `cap_window` and `overdraft` columns.
+    pub async fn load(path: &Path) -> io::Result<Self> {
+        let file = tokio::fs::File::open(path).await?;
+        let mut reader = csv_async::AsyncReaderBuilder::new()
+            .flexible(true)
+            .trim(csv_async::Trim::All)
+            .create_deserializer(file);
+        let invalid = |err: csv_async::Error| io::Error::new(io::ErrorKind::InvalidData, err);
+        let headers = reader.headers().await.map_err(invalid)?.clone();
+        let mut record = csv_async::StringRecord::new();
+        let mut clients = HashMap::new();
+        while reader.read_record(&mut record).await.map_err(invalid)? {
+            let limits = record
+                .deserialize::<ClientLimits>(Some(&headers))
+                .map_err(invalid)?;
+            if limits.withdrawal_cap.is_some() != limits.cap_window.is_some() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "client `{}` needs both `withdrawal_cap` and `cap_window`",
+                        limits.client_id
+                    ),
+                ));
+            }
+            clients.insert(limits.client_id, limits);
+        }
+        Ok(Self { clients })
+    }
+
+    pub fn get(&self, client_id: u16) -> Option<&ClientLimits> {
+        self.clients.get(&client_id)
+    }
+
+    /// How many of its latest transactions an account has to remember for its rolling cap.
+    pub fn window(&self, client_id: u16) -> usize {
+        self.get(client_id)
+            .and_then(ClientLimits::rolling_cap)
+            .map(|(_, window)| window)
+            .unwrap_or_default()
+    }
+}
+
+impl FromIterator<ClientLimits> for Limits {
+    fn from_iter<T: IntoIterator<Item = ClientLimits>>(iter: T) -> Self {
+        Self {
+            clients: iter
+                .into_iter()
+                .map(|limits| (limits.client_id, limits))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rust_decimal_macros::dec;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_load() {
+        let path =
+            std::env::temp_dir().join(format!("leviathan-limits-{}.csv", std::process::id()));
+        tokio::fs::write(
+            &path,
+            "client,max_withdrawal,withdrawal_cap,cap_window,overdraft\n1,100,,,\n2,,50,3,25\n",
+        )
+        .await
+        .unwrap();
+        let limits = Limits::load(&path).await;
+        tokio::fs::write(&path, "client,withdrawal_cap\n3,10\n")
+            .await
+            .unwrap();
+        let incomplete = Limits::load(&path).await;
+        tokio::fs::remove_file(&path).await.unwrap();
+
+        let limits = limits.unwrap();
+        assert_eq!(limits.get(1).unwrap().max_withdrawal, Some(dec!(100)));
+        assert_eq!(limits.get(1).unwrap().overdraft, None);
+        assert_eq!(limits.get(2).unwrap().rolling_cap(), Some((dec!(50), 3)));
+        assert_eq!(limits.window(2), 3);
+        assert_eq!(limits.window(3), 0);
+        assert_eq!(incomplete.unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -1,3 +1,10 @@
+pub mod bookkeeping;
+pub mod config;
 pub mod domain;
 pub mod error;
+pub mod fees;
+pub mod handle;
+pub mod invariants;
 pub mod ledger;
+pub mod limits;
+pub mod rules;
@@ -0,0 +1,236 @@
+use std::{collections::VecDeque, io, path::Path};
+
+use rust_decimal::Decimal;
+use serde::Deserialize;
+
+use crate::engine::domain::{TransactionEvent, TransactionType};
+
+/// What happens to a transaction that matches a [`Rule`].
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    /// Refuse the transaction.
+    Reject,
+    /// Apply the transaction, but report it on stderr.
+    Flag,
+    /// Refuse the transaction and lock the account.
+    Freeze,
+}
+
+/// The condition a [`Rule`] checks.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Check {
+    /// More than `max` transactions of type `type` among the last `window` transactions of
+    /// the account, counting the checked one.
+    Velocity {
+        #[serde(rename = "type")]
+        transaction_type: TransactionType,
+        max: usize,
+        window: usize,
+    },
+    /// An amount greater than `threshold`, optionally only for transactions of type `type`.
+    LargeAmount {
+        threshold: Decimal,
+        #[serde(rename = "type", default)]
+        transaction_type: Option<TransactionType>,
+    },
+    /// A dispute of a deposit that was followed by a withdrawal, all within the last
+    /// `window` transactions of the account.
+    DepositWithdrawDispute { window: usize },
+}
+
+/// A named risk check and the action to take when a transaction matches it.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct Rule {
+    pub name: String,
+    pub action: Action,
+    #[serde(flatten)]
+    pub check: Check,
+}
+
+impl Rule {
+    fn matches(&self, recent: &VecDeque<TransactionEvent>, event: &TransactionEvent) -> bool {
+        match &self.check {
+            Check::Velocity {
+                transaction_type,
+                max,
+                window,
+            } => {
+                let previous = recent
+                    .iter()
+                    .rev()
+                    .take(window.saturating_sub(1))
+                    .filter(|recent| recent.transaction_type == *transaction_type)
+                    .count();
+                let current = usize::from(event.transaction_type == *transaction_type);
+                previous + current > *max
+            }
+            Check::LargeAmount {
+                threshold,
+                transaction_type,
+            } => {
+                let applies = match transaction_type {
+                    Some(kind) => *kind == event.transaction_type,
+                    None => true,
+                };
+                applies && matches!(event.amount, Some(amount) if amount > *threshold)
+            }
+            Check::DepositWithdrawDispute { window } => {
+                if event.transaction_type != TransactionType::Dispute {
+                    return false;
+                }
+                let recent = recent
+                    .iter()
+                    .rev()
+                    .take(window.saturating_sub(1))
+                    .collect::<Vec<_>>();
+                let deposit = recent.iter().position(|recent| {
+                    recent.transaction_type == TransactionType::Deposit
+                        && recent.tx_id == event.tx_id
+                });
+                match deposit {
+                    // `recent` runs from the latest transaction back, so anything before the
+                    // deposit happened after it.
+                    Some(deposit) => recent[..deposit]
+                        .iter()
+                        .any(|recent| recent.transaction_type == TransactionType::Withdrawal),
+                    None => false,
+                }
+            }
+        }
+    }
+
+    fn window(&self) -> usize {
+        match self.check {
+            Check::Velocity { window, .. } | Check::DepositWithdrawDispute { window } => window,
+            Check::LargeAmount { .. } => 0,
+        }
+    }
+}
+
+/// The outcome of checking a transaction against a [`RuleSet`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict<'a> {
+    /// Apply the transaction, which the named rules flagged, if any.
+    Allow(Vec<&'a str>),
+    Reject(&'a str),
+    Freeze(&'a str),
+}
+
+/// The risk rules every transaction is checked against before it is applied.
+///
+/// Rules are read from a JSON file, e.g.
+///
+/// ```json
+/// {"rules": [
+///     {"name": "withdrawal-velocity", "kind": "velocity", "type": "withdrawal", "max": 3, "window": 5, "action": "freeze"},
+///     {"name": "large-deposit", "kind": "large_amount", "type": "deposit", "threshold": "10000", "action": "flag"},
+///     {"name": "bust-out", "kind": "deposit_withdraw_dispute", "window": 10, "action": "reject"}
+/// ]}
+/// ```
+#[derive(Debug, Default, Deserialize, Clone, PartialEq)]
+pub struct RuleSet {
+    pub rules: Vec<Rule>,
+}
+
+impl RuleSet {
+    /// Reads a rule set from the JSON file at `path`.
+    pub async fn load(path: &Path) -> io::Result<Self> {
+        let data = tokio::fs::read(path).await?;
+        serde_json::from_slice(&data).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// How many of its latest transactions an account has to remember for the rules.
+    pub fn window(&self) -> usize {
+        self.rules
+            .iter()
+            .map(Rule::window)
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Checks `event` against every rule, given the latest transactions the account applied.
+    ///
+    /// The first rule that rejects or freezes decides the verdict, otherwise every rule
+    /// that flags is named in it.
+    pub fn evaluate(
+        &self,
+        recent: &VecDeque<TransactionEvent>,
+        event: &TransactionEvent,
+    ) -> Verdict<'_> {
+        let mut flags = Vec::new();
+        for rule in self.rules.iter().filter(|rule| rule.matches(recent, event)) {
+            match rule.action {
+                Action::Flag => flags.push(rule.name.as_str()),
+                Action::Reject => return Verdict::Reject(&rule.name),
+                Action::Freeze => return Verdict::Freeze(&rule.name),
+            }
+        }
+        Verdict::Allow(flags)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rust_decimal_macros::dec;
+
+    use super::*;
+
+    fn event(
+        transaction_type: TransactionType,
+        tx_id: u32,
+        amount: Option<Decimal>,
+    ) -> TransactionEvent {
+        TransactionEvent {
+            client_id: 1,
+            tx_id,
+            transaction_type,
+            amount,
+            counterparty: None,
+        }
+    }
+
+    #[test]
+    fn test_rules() {
+        let rules: RuleSet = serde_json::from_str(
+            r#"{"rules": [
+                {"name": "velocity", "kind": "velocity", "type": "withdrawal", "max": 2, "window": 3, "action": "freeze"},
+                {"name": "large", "kind": "large_amount", "threshold": "100", "action": "flag"},
+                {"name": "bust-out", "kind": "deposit_withdraw_dispute", "window": 4, "action": "reject"}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(rules.window(), 4);
+
+        let mut recent = VecDeque::new();
+        recent.push_back(event(TransactionType::Deposit, 1, Some(dec!(500))));
+        recent.push_back(event(TransactionType::Withdrawal, 2, Some(dec!(1))));
+        let withdrawal = event(TransactionType::Withdrawal, 3, Some(dec!(1)));
+        assert_eq!(rules.evaluate(&recent, &withdrawal), Verdict::Allow(vec![]));
+        recent.push_back(withdrawal);
+        assert_eq!(
+            rules.evaluate(
+                &recent,
+                &event(TransactionType::Withdrawal, 4, Some(dec!(1)))
+            ),
+            Verdict::Freeze("velocity")
+        );
+        assert_eq!(
+            rules.evaluate(
+                &recent,
+                &event(TransactionType::Deposit, 4, Some(dec!(1000)))
+            ),
+            Verdict::Allow(vec!["large"])
+        );
+        assert_eq!(
+            rules.evaluate(&recent, &event(TransactionType::Dispute, 1, None)),
+            Verdict::Reject("bust-out")
+        );
+        recent.push_back(event(TransactionType::Deposit, 5, Some(dec!(1))));
+        assert_eq!(
+            rules.evaluate(&recent, &event(TransactionType::Dispute, 1, None)),
+            Verdict::Allow(vec![])
+        );
+    }
+}
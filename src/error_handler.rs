@@ -1,6 +1,17 @@
-use std::{fmt::Debug, future::Future, sync::Arc};
+use std::{fmt::Debug, future::Future, path::Path, sync::Arc};
 
 use futures::future::BoxFuture;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use tokio::{fs::File, io, sync::Mutex};
+
+use crate::{
+    engine::{
+        domain::TransactionType,
+        ledger::{Account, Rejection},
+    },
+    listener::error::RecordError,
+};
 
 /// An asynchronous handler of an error.
 pub trait ErrorHandler<E> {
@@ -85,3 +96,183 @@ where
         Box::pin(async {})
     }
 }
+
+/// Writes records that failed to decode to a quarantine CSV file, so they can be
+/// corrected and resubmitted.
+pub struct QuarantineErrorHandler {
+    writer: Mutex<csv_async::AsyncSerializer<File>>,
+}
+
+#[derive(Serialize)]
+struct QuarantinedRecord<'a> {
+    source: &'a str,
+    line: Option<u64>,
+    byte: Option<u64>,
+    record: Option<String>,
+    error: String,
+}
+
+impl QuarantineErrorHandler {
+    /// Creates (or truncates) the quarantine file at `path`.
+    pub async fn create<P>(path: P) -> io::Result<Arc<Self>>
+    where
+        P: AsRef<Path>,
+    {
+        let file = File::create(path).await?;
+        Ok(Arc::new(Self {
+            writer: Mutex::new(
+                csv_async::AsyncWriterBuilder::new()
+                    .has_headers(true)
+                    .create_serializer(file),
+            ),
+        }))
+    }
+}
+
+impl ErrorHandler<RecordError> for QuarantineErrorHandler {
+    fn handle_error(self: Arc<Self>, error: RecordError) -> BoxFuture<'static, ()> {
+        Box::pin(async move {
+            let record = match &error.record {
+                Some(fields) => match csv_line(fields).await {
+                    Ok(line) => Some(line),
+                    Err(err) => {
+                        eprintln!("Failed to quarantine record: {err}. Record error: {error}");
+                        return;
+                    }
+                },
+                None => None,
+            };
+            let row = QuarantinedRecord {
+                source: &error.source_name,
+                line: error.line,
+                byte: error.byte,
+                record,
+                error: error.error.to_string(),
+            };
+            let mut writer = self.writer.lock().await;
+            if let Err(err) = writer.serialize(row).await {
+                eprintln!("Failed to quarantine record: {err}. Record error: {error}");
+                return;
+            }
+            if let Err(err) = writer.flush().await {
+                eprintln!("Failed to flush quarantine file: {err}");
+            }
+        })
+    }
+}
+
+/// Encodes `fields` as one line of CSV, quoting them where needed so that the line can be
+/// resubmitted as is.
+async fn csv_line(fields: &[String]) -> Result<String, csv_async::Error> {
+    let mut line = Vec::new();
+    {
+        let mut writer = csv_async::AsyncWriterBuilder::new()
+            .flexible(true)
+            .create_writer(&mut line);
+        writer.write_record(fields).await?;
+        writer.flush().await?;
+    }
+    let line = String::from_utf8_lossy(&line);
+    Ok(line.trim_end_matches(&['\r', '\n'][..]).to_owned())
+}
+
+/// Writes transactions rejected by the ledger, and the reason for rejecting them,
+/// to a CSV report.
+pub struct RejectionReport {
+    writer: Mutex<csv_async::AsyncSerializer<File>>,
+}
+
+#[derive(Serialize)]
+struct RejectedTransaction<'a> {
+    #[serde(rename = "type")]
+    transaction_type: &'a TransactionType,
+    client: u16,
+    tx: u32,
+    amount: Option<Decimal>,
+    reason: String,
+}
+
+impl RejectionReport {
+    /// Creates (or truncates) the report file at `path`.
+    pub async fn create<P>(path: P) -> io::Result<Arc<Self>>
+    where
+        P: AsRef<Path>,
+    {
+        let file = File::create(path).await?;
+        Ok(Arc::new(Self {
+            writer: Mutex::new(
+                csv_async::AsyncWriterBuilder::new()
+                    .has_headers(true)
+                    .create_serializer(file),
+            ),
+        }))
+    }
+}
+
+impl ErrorHandler<Rejection<Account>> for RejectionReport {
+    fn handle_error(self: Arc<Self>, rejection: Rejection<Account>) -> BoxFuture<'static, ()> {
+        Box::pin(async move {
+            let row = RejectedTransaction {
+                transaction_type: &rejection.event.transaction_type,
+                client: rejection.id,
+                tx: rejection.tx_id,
+                amount: rejection.event.amount,
+                reason: rejection.error.to_string(),
+            };
+            let mut writer = self.writer.lock().await;
+            if let Err(err) = writer.serialize(row).await {
+                eprintln!("Failed to report rejected transaction: {err}");
+                return;
+            }
+            if let Err(err) = writer.flush().await {
+                eprintln!("Failed to flush rejection report: {err}");
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::StreamExt;
+
+    use super::*;
+    use crate::{
+        engine::domain::TransactionEvent,
+        listener::{csv_reader, UpdateListener},
+    };
+
+    async fn quarantine<L: UpdateListener<TransactionEvent, RecordError>>(
+        mut listener: L,
+        eh: Arc<QuarantineErrorHandler>,
+    ) {
+        let stream = listener.as_stream();
+        tokio::pin!(stream);
+        while let Some(update) = stream.next().await {
+            update.on_error(Arc::clone(&eh)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_quarantine_error_handler() {
+        let path =
+            std::env::temp_dir().join(format!("leviathan-quarantine-{}.csv", std::process::id()));
+        let eh = QuarantineErrorHandler::create(&path).await.unwrap();
+        let data = "type,client,tx,amount\ndeposit,1,1,5.0\nrefund,1,2,1.0\ndeposit,1,3,\"1,5\"\n";
+
+        quarantine(csv_reader("inline.csv", data.as_bytes()), eh).await;
+
+        let contents = tokio::fs::read_to_string(&path).await.unwrap();
+        tokio::fs::remove_file(&path).await.unwrap();
+        let mut lines = contents.lines();
+        assert_eq!(lines.next(), Some("source,line,byte,record,error"));
+        assert!(lines
+            .next()
+            .unwrap()
+            .starts_with("inline.csv,3,38,\"refund,1,2,1.0\","));
+        assert!(lines
+            .next()
+            .unwrap()
+            .starts_with("inline.csv,4,53,\"deposit,1,3,\"\"1,5\"\"\","));
+        assert_eq!(lines.next(), None);
+    }
+}
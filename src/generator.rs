@@ -0,0 +1,385 @@
+use std::{num::NonZeroU16, str::FromStr};
+
+use rand::{distributions::WeightedIndex, rngs::StdRng, Rng, SeedableRng};
+use rust_decimal::Decimal;
+use tokio::io::{self, AsyncWrite, AsyncWriteExt, BufWriter};
+
+/// The CSV header of generated files.
+pub const HEADER: &str = "type,client,tx,amount,counterparty";
+
+/// How many of the latest deposits and transfers are kept as dispute candidates.
+const DISPUTABLE: usize = 10_000;
+
+/// Relative weights of the transaction types of the rows that are not disputes, resolves
+/// or chargebacks, written as e.g. `deposit=6,withdrawal=3,transfer=1`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mix {
+    pub deposit: u32,
+    pub withdrawal: u32,
+    pub transfer: u32,
+}
+
+impl Default for Mix {
+    fn default() -> Self {
+        Self {
+            deposit: 6,
+            withdrawal: 3,
+            transfer: 1,
+        }
+    }
+}
+
+impl FromStr for Mix {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut mix = Self {
+            deposit: 0,
+            withdrawal: 0,
+            transfer: 0,
+        };
+        for part in s.split(',') {
+            let (name, weight) = part
+                .split_once('=')
+                .ok_or_else(|| format!("expected `type=weight`, got `{part}`"))?;
+            let weight = weight
+                .trim()
+                .parse()
+                .map_err(|err| format!("invalid weight `{weight}`: {err}"))?;
+            match name.trim() {
+                "deposit" => mix.deposit = weight,
+                "withdrawal" => mix.withdrawal = weight,
+                "transfer" => mix.transfer = weight,
+                other => {
+                    return Err(format!(
+                        "unknown type `{other}`, expected `deposit`, `withdrawal` or `transfer`"
+                    ))
+                }
+            }
+        }
+        let total = mix
+            .deposit
+            .checked_add(mix.withdrawal)
+            .and_then(|total| total.checked_add(mix.transfer))
+            .ok_or_else(|| "the weights add up to more than u32::MAX".to_owned())?;
+        if total == 0 {
+            return Err("at least one weight must be positive".to_owned());
+        }
+        Ok(mix)
+    }
+}
+
+/// What a [`Generator`] produces.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeneratorConfig {
+    /// Clients are numbered from 1 to `clients`.
+    pub clients: NonZeroU16,
+    /// Number of rows, not counting the header.
+    pub rows: u32,
+    pub mix: Mix,
+    /// Chance of a row disputing a recent deposit or transfer, and also of a row settling
+    /// an open dispute.
+    pub dispute_rate: f64,
+    /// Chance of a dispute being settled with a chargeback rather than a resolve.
+    pub chargeback_rate: f64,
+    /// Chance of a row being one that cannot be decoded.
+    pub malformed_rate: f64,
+    /// The same seed and configuration always produce the same rows.
+    pub seed: u64,
+}
+
+impl Default for GeneratorConfig {
+    fn default() -> Self {
+        Self {
+            clients: NonZeroU16::new(100).unwrap(),
+            rows: 1_000,
+            mix: Mix::default(),
+            dispute_rate: 0.02,
+            chargeback_rate: 0.25,
+            malformed_rate: 0.0,
+            seed: 0,
+        }
+    }
+}
+
+/// Generates random CSV transaction rows in the format [`polling`] reads.
+///
+/// Transaction IDs increase by one per deposit, withdrawal or transfer. Disputes refer to
+/// recent deposits, or transfers from the receiving side, and every dispute is settled at
+/// most once. A client that was charged back, and so is locked by the engine, makes no
+/// further transactions, not even to settle its open disputes, and the last one left is
+/// never charged back. Transfers are made between clients that already deposited.
+/// Withdrawals and transfers are not checked against balances, so some of them are
+/// rejected by the engine.
+///
+/// [`polling`]: crate::listener::polling
+pub struct Generator {
+    config: GeneratorConfig,
+    rng: StdRng,
+    types: WeightedIndex<u32>,
+    rows: u32,
+    next_tx: u32,
+    /// Clients that were not charged back.
+    active: Vec<u16>,
+    /// Active clients that made a deposit, and whether each client did, by client ID.
+    funded: Vec<u16>,
+    deposited: Vec<bool>,
+    disputable: Vec<(u16, u32)>,
+    disputed: Vec<(u16, u32)>,
+}
+
+impl Generator {
+    /// # Panics
+    ///
+    /// If a rate of `config` is not between 0 and 1, or every weight of its mix is zero.
+    pub fn new(config: GeneratorConfig) -> Self {
+        for rate in [
+            config.dispute_rate,
+            config.chargeback_rate,
+            config.malformed_rate,
+        ] {
+            assert!((0.0..=1.0).contains(&rate), "rate {rate} is not in 0..=1");
+        }
+        let types = WeightedIndex::new([
+            config.mix.deposit,
+            config.mix.withdrawal,
+            config.mix.transfer,
+        ])
+        .expect("the mix has a positive weight");
+        Self {
+            rng: StdRng::seed_from_u64(config.seed),
+            types,
+            rows: 0,
+            next_tx: 1,
+            active: (1..=config.clients.get()).collect(),
+            funded: Vec::new(),
+            deposited: vec![false; usize::from(config.clients.get()) + 1],
+            disputable: Vec::new(),
+            disputed: Vec::new(),
+            config,
+        }
+    }
+
+    /// Writes the header and every row to `writer`.
+    pub async fn write<W>(self, writer: W) -> io::Result<()>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let mut writer = BufWriter::new(writer);
+        writer.write_all(HEADER.as_bytes()).await?;
+        writer.write_all(b"\n").await?;
+        for row in self {
+            writer.write_all(row.as_bytes()).await?;
+            writer.write_all(b"\n").await?;
+        }
+        writer.flush().await
+    }
+
+    fn client(&mut self) -> u16 {
+        self.active[self.rng.gen_range(0..self.active.len())]
+    }
+
+    fn funded_client(&mut self) -> u16 {
+        self.funded[self.rng.gen_range(0..self.funded.len())]
+    }
+
+    fn amount(&mut self) -> Decimal {
+        Decimal::new(self.rng.gen_range(1..=10_000_000), 4)
+    }
+
+    fn remember(&mut self, client: u16, tx: u32) {
+        if self.disputable.len() < DISPUTABLE {
+            self.disputable.push((client, tx));
+        } else {
+            let index = self.rng.gen_range(0..DISPUTABLE);
+            self.disputable[index] = (client, tx);
+        }
+    }
+
+    fn malformed(&mut self) -> String {
+        let client = self.client();
+        let tx = self.next_tx;
+        match self.rng.gen_range(0..4) {
+            0 => format!("refund,{client},{tx},1.0,"),
+            1 => format!("deposit,{client},tx{tx},1.0,"),
+            2 => format!("deposit,{client},{tx},1.0.0,"),
+            _ => format!("withdrawal,-{client},{tx},1.0,"),
+        }
+    }
+
+    fn settle(&mut self) -> String {
+        let index = self.rng.gen_range(0..self.disputed.len());
+        let (client, tx) = self.disputed.swap_remove(index);
+        let kind = if self.active.len() > 1 && self.rng.gen_bool(self.config.chargeback_rate) {
+            self.active.retain(|active| *active != client);
+            self.funded.retain(|funded| *funded != client);
+            self.disputable
+                .retain(|(disputable, _)| *disputable != client);
+            self.disputed.retain(|(disputed, _)| *disputed != client);
+            "chargeback"
+        } else {
+            "resolve"
+        };
+        format!("{kind},{client},{tx},,")
+    }
+
+    fn dispute(&mut self) -> String {
+        let index = self.rng.gen_range(0..self.disputable.len());
+        let (client, tx) = self.disputable.swap_remove(index);
+        self.disputed.push((client, tx));
+        format!("dispute,{client},{tx},,")
+    }
+
+    fn transaction(&mut self) -> String {
+        let tx = self.next_tx;
+        self.next_tx += 1;
+        let amount = self.amount();
+        match self.rng.sample(&self.types) {
+            1 => {
+                let client = self.client();
+                format!("withdrawal,{client},{tx},{amount},")
+            }
+            2 if self.funded.len() > 1 => {
+                let client = self.funded_client();
+                let counterparty = loop {
+                    let counterparty = self.funded_client();
+                    if counterparty != client {
+                        break counterparty;
+                    }
+                };
+                self.remember(counterparty, tx);
+                format!("transfer,{client},{tx},{amount},{counterparty}")
+            }
+            _ => {
+                let client = self.client();
+                if !self.deposited[usize::from(client)] {
+                    self.deposited[usize::from(client)] = true;
+                    self.funded.push(client);
+                }
+                self.remember(client, tx);
+                format!("deposit,{client},{tx},{amount},")
+            }
+        }
+    }
+}
+
+impl Iterator for Generator {
+    /// A CSV row, without the line terminator.
+    type Item = String;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.rows == self.config.rows {
+            return None;
+        }
+        self.rows += 1;
+
+        let row = if self.rng.gen_bool(self.config.malformed_rate) {
+            self.malformed()
+        } else if !self.disputed.is_empty() && self.rng.gen_bool(self.config.dispute_rate) {
+            self.settle()
+        } else if !self.disputable.is_empty() && self.rng.gen_bool(self.config.dispute_rate) {
+            self.dispute()
+        } else {
+            self.transaction()
+        };
+        Some(row)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::StreamExt;
+
+    use super::*;
+    use crate::listener::{csv_reader, AsUpdateStream};
+
+    async fn generate(config: GeneratorConfig) -> Vec<u8> {
+        let mut csv = Vec::new();
+        Generator::new(config).write(&mut csv).await.unwrap();
+        csv
+    }
+
+    #[tokio::test]
+    async fn test_generator() {
+        let config = GeneratorConfig {
+            clients: NonZeroU16::new(5).unwrap(),
+            rows: 500,
+            dispute_rate: 0.2,
+            malformed_rate: 0.1,
+            seed: 7,
+            ..GeneratorConfig::default()
+        };
+        let csv = generate(config.clone()).await;
+        assert_eq!(csv, generate(config.clone()).await);
+        assert_ne!(
+            csv,
+            generate(GeneratorConfig {
+                seed: 8,
+                ..config.clone()
+            })
+            .await
+        );
+
+        let text = String::from_utf8(csv.clone()).unwrap();
+        assert!(text.starts_with("type,client,tx,amount,counterparty\n"));
+        assert_eq!(text.lines().count(), 501);
+        for kind in [
+            "deposit,",
+            "withdrawal,",
+            "transfer,",
+            "dispute,",
+            "resolve,",
+        ] {
+            assert!(text.contains(kind), "no `{kind}` row");
+        }
+
+        // Charged back clients make no further transactions, including settling disputes.
+        let mut charged_back = Vec::new();
+        for row in text.lines().skip(1) {
+            let mut fields = row.split(',');
+            let (kind, client) = (fields.next().unwrap(), fields.next().unwrap());
+            assert!(
+                !charged_back.contains(&client),
+                "`{row}` after a chargeback"
+            );
+            if kind == "chargeback" {
+                charged_back.push(client);
+            }
+        }
+
+        let mut listener = csv_reader("generated", std::io::Cursor::new(csv));
+        let results = listener.as_stream().collect::<Vec<_>>().await;
+        assert_eq!(results.len(), 500);
+        let malformed = results.iter().filter(|result| result.is_err()).count();
+        assert!((20..80).contains(&malformed), "{malformed} malformed rows");
+
+        let csv = generate(GeneratorConfig {
+            malformed_rate: 0.0,
+            ..config
+        })
+        .await;
+        let mut listener = csv_reader("generated", std::io::Cursor::new(csv));
+        assert!(
+            listener
+                .as_stream()
+                .all(|result| async move { result.is_ok() })
+                .await
+        );
+    }
+
+    #[test]
+    fn test_mix() {
+        assert_eq!(
+            "withdrawal=2, deposit=5".parse::<Mix>(),
+            Ok(Mix {
+                deposit: 5,
+                withdrawal: 2,
+                transfer: 0,
+            })
+        );
+        assert!("deposit=0".parse::<Mix>().is_err());
+        assert!("refund=1".parse::<Mix>().is_err());
+        assert!("deposit".parse::<Mix>().is_err());
+        assert!("deposit=4294967295,withdrawal=1".parse::<Mix>().is_err());
+    }
+}
@@ -0,0 +1,194 @@
+use std::{convert::Infallible, fmt::Display, net::SocketAddr, str::FromStr, sync::Arc};
+
+use futures::future::BoxFuture;
+use hyper::{
+    header::{self, HeaderValue},
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use serde::Serialize;
+
+use crate::engine::{
+    error::StoreError,
+    ledger::{Aggregate, Ledger},
+};
+
+/// Read-only JSON API over the aggregates in a [`Ledger`], for querying balances while
+/// the engine runs:
+///
+/// - `GET /accounts`: all snapshots, ordered by ID.
+/// - `GET /accounts/{id}`: the snapshot of one aggregate.
+/// - `GET /accounts/{id}/transactions`: the transactions the aggregate keeps a record of.
+pub struct QueryServer {
+    local_addr: SocketAddr,
+    server: BoxFuture<'static, hyper::Result<()>>,
+}
+
+impl QueryServer {
+    /// Binds the server to `addr`; port `0` lets the OS pick a free port.
+    pub fn bind<A, L>(addr: &SocketAddr, ledger: Arc<L>) -> hyper::Result<Self>
+    where
+        A: Aggregate + Send + Sync + 'static,
+        A::ID: FromStr + Display,
+        A::EventData: Serialize,
+        A::Snapshot: Serialize,
+        A::Error: Display + Send,
+        L: Ledger<A> + Send + Sync + 'static,
+    {
+        let make_service = make_service_fn(move |_| {
+            let ledger = Arc::clone(&ledger);
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    route::<A, L>(Arc::clone(&ledger), request)
+                }))
+            }
+        });
+        let server = Server::try_bind(addr)?.serve(make_service);
+        Ok(Self {
+            local_addr: server.local_addr(),
+            server: Box::pin(server),
+        })
+    }
+
+    /// The address the server is listening on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Serves requests until the server fails.
+    pub async fn run(self) -> hyper::Result<()> {
+        self.server.await
+    }
+}
+
+async fn route<A, L>(ledger: Arc<L>, request: Request<Body>) -> Result<Response<Body>, Infallible>
+where
+    A: Aggregate + Send + Sync + 'static,
+    A::ID: FromStr + Display,
+    A::EventData: Serialize,
+    A::Snapshot: Serialize,
+    A::Error: Display,
+    L: Ledger<A>,
+{
+    if request.method() != Method::GET {
+        return Ok(error(
+            StatusCode::METHOD_NOT_ALLOWED,
+            "Only GET is supported",
+        ));
+    }
+    let segments = request
+        .uri()
+        .path()
+        .trim_matches('/')
+        .split('/')
+        .collect::<Vec<_>>();
+    let response = match segments.as_slice() {
+        ["accounts"] => json(ledger.all_snapshots().await),
+        ["accounts", id] => match id.parse().ok() {
+            Some(id) => json(ledger.snapshot(id).await),
+            None => error(StatusCode::BAD_REQUEST, format!("Invalid ID `{id}`")),
+        },
+        ["accounts", id, "transactions"] => match id.parse().ok() {
+            Some(id) => json(ledger.transactions(id).await),
+            None => error(StatusCode::BAD_REQUEST, format!("Invalid ID `{id}`")),
+        },
+        _ => error(StatusCode::NOT_FOUND, "Not found"),
+    };
+    Ok(response)
+}
+
+fn json<T, ID, E>(result: Result<T, StoreError<ID, E>>) -> Response<Body>
+where
+    T: Serialize,
+    ID: Display,
+    E: Display,
+{
+    match result.map(|body| serde_json::to_vec(&body)) {
+        Ok(Ok(body)) => response(StatusCode::OK, body),
+        Ok(Err(err)) => error(StatusCode::INTERNAL_SERVER_ERROR, err),
+        Err(err @ StoreError::NotFound(_)) => error(StatusCode::NOT_FOUND, err),
+        Err(err @ StoreError::Unsupported(_)) => error(StatusCode::NOT_IMPLEMENTED, err),
+        Err(err) => error(StatusCode::INTERNAL_SERVER_ERROR, err),
+    }
+}
+
+pub(crate) fn error(status: StatusCode, message: impl Display) -> Response<Body> {
+    let body = serde_json::json!({ "error": message.to_string() });
+    response(status, body.to_string().into_bytes())
+}
+
+pub(crate) fn response(status: StatusCode, body: Vec<u8>) -> Response<Body> {
+    let mut response = Response::new(Body::from(body));
+    *response.status_mut() = status;
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+    response
+}
+
+#[cfg(test)]
+mod test {
+    use rust_decimal_macros::dec;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpStream,
+    };
+
+    use super::*;
+    use crate::engine::{
+        domain::{TransactionEvent, TransactionType},
+        ledger::{Account, InMemoryLedger},
+    };
+
+    async fn get(addr: SocketAddr, path: &str) -> (u16, serde_json::Value) {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let request =
+            format!("GET {path} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n");
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+
+        let status = response[9..12].parse().unwrap();
+        let (_, body) = response.split_once("\r\n\r\n").unwrap();
+        (status, serde_json::from_str(body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_query_server() {
+        let ledger = InMemoryLedger::<Account>::new();
+        for (tx_id, amount) in [(1, dec!(3)), (2, dec!(1.5))] {
+            let event = TransactionEvent {
+                client_id: 4,
+                tx_id,
+                transaction_type: TransactionType::Deposit,
+                amount: Some(amount),
+                counterparty: None,
+            };
+            Arc::clone(&ledger)
+                .process_transaction(4, tx_id, event)
+                .await
+                .unwrap();
+        }
+        let server = QueryServer::bind(&([127, 0, 0, 1], 0).into(), ledger).unwrap();
+        let addr = server.local_addr();
+        tokio::spawn(server.run());
+
+        let (status, body) = get(addr, "/accounts").await;
+        assert_eq!(status, 200);
+        assert_eq!(body[0]["client"], 4);
+
+        let (status, body) = get(addr, "/accounts/4").await;
+        assert_eq!(status, 200);
+        assert_eq!(body["total"], "4.5");
+
+        let (status, body) = get(addr, "/accounts/4/transactions").await;
+        assert_eq!(status, 200);
+        assert_eq!(body.as_array().unwrap().len(), 2);
+        assert_eq!(body[1]["tx"], 2);
+
+        assert_eq!(get(addr, "/accounts/5").await.0, 404);
+        assert_eq!(get(addr, "/accounts/x").await.0, 400);
+        assert_eq!(get(addr, "/clients").await.0, 404);
+    }
+}
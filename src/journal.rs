@@ -0,0 +1,67 @@
+use std::{path::Path, sync::Arc};
+
+use futures::future::BoxFuture;
+use serde::Serialize;
+use tokio::{fs::File, io, sync::Mutex};
+
+use crate::{engine::ledger::Aggregate, TransactionObserver};
+
+/// Append-only CSV record of the transaction events handed to the ledger, in order.
+///
+/// A journal uses the same columns as the input files, so replaying it through a
+/// fresh ledger rebuilds the state it was written from.
+pub struct Journal {
+    writer: Mutex<csv_async::AsyncSerializer<File>>,
+}
+
+impl Journal {
+    /// Creates (or truncates) the journal file at `path`.
+    pub async fn create<P>(path: P) -> io::Result<Arc<Self>>
+    where
+        P: AsRef<Path>,
+    {
+        let file = File::create(path).await?;
+        Ok(Arc::new(Self {
+            writer: Mutex::new(
+                csv_async::AsyncWriterBuilder::new()
+                    .has_headers(true)
+                    .create_serializer(file),
+            ),
+        }))
+    }
+
+    /// Appends `event` to the journal.
+    pub async fn record<E>(&self, event: &E) -> Result<(), csv_async::Error>
+    where
+        E: Serialize,
+    {
+        self.writer.lock().await.serialize(event).await
+    }
+
+    /// Writes all recorded events through to the journal file.
+    pub async fn flush(&self) -> io::Result<()> {
+        self.writer.lock().await.flush().await
+    }
+}
+
+impl<A> TransactionObserver<A> for Journal
+where
+    A: Aggregate,
+    A::EventData: Serialize,
+{
+    fn observe<'a>(&'a self, event: &'a A::EventData, _accepted: bool) -> BoxFuture<'a, ()> {
+        Box::pin(async move {
+            if let Err(err) = self.record(event).await {
+                eprintln!("Failed to journal transaction: {err}");
+            }
+        })
+    }
+
+    fn finish<'a>(&'a self, _snapshots: &'a [A::Snapshot]) -> BoxFuture<'a, ()> {
+        Box::pin(async move {
+            if let Err(err) = self.flush().await {
+                eprintln!("Failed to flush journal: {err}");
+            }
+        })
+    }
+}
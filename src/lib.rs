@@ -1,123 +1,424 @@
+pub mod checkpoint;
 pub mod engine;
 pub mod error_handler;
+#[cfg(feature = "generate")]
+pub mod generator;
+#[cfg(feature = "http")]
+pub mod http;
+pub mod journal;
 pub mod listener;
+pub mod output;
+pub mod reconcile;
+pub mod stats;
+pub mod validation;
 
 use std::future::Future;
-use std::{fmt::Debug, sync::Arc};
+use std::{
+    fmt::{Debug, Display},
+    sync::Arc,
+};
 
 use futures::{future::BoxFuture, FutureExt, StreamExt};
-use tokio::io;
+use tokio::{io, sync::watch};
 use tokio_stream::wrappers::UnboundedReceiverStream;
+use tokio_util::sync::CancellationToken;
 
 use crate::engine::domain::AccountSnapshot;
 use crate::{
     engine::{
         domain::TransactionEvent,
-        ledger::{Account, Aggregate, InMemoryLedger, Ledger},
+        error::StoreError,
+        ledger::{Account, Aggregate, InMemoryLedger, Ledger, Rejection, Route},
     },
-    error_handler::LoggingErrorHandler,
+    error_handler::{ErrorHandler, LoggingErrorHandler},
     listener::{
         handler::{Dispatcher, DispatcherHandler, DispatcherHandlerRx},
         update::UpdateWithCx,
         UpdateListener,
     },
+    output::{write_snapshots, OutputFormat},
 };
 
-pub trait SnapshotHandler {
-    fn handle(self: Arc<Self>, snapshot: Vec<AccountSnapshot>) -> BoxFuture<'static, ()>
+pub trait SnapshotHandler<S> {
+    fn handle(self: Arc<Self>, snapshot: Vec<S>) -> BoxFuture<'static, ()>
     where
-        AccountSnapshot: Send + 'static;
+        S: Send + 'static;
 }
 
-impl<F, Fut> SnapshotHandler for F
+impl<S, F, Fut> SnapshotHandler<S> for F
 where
-    F: Fn(Vec<AccountSnapshot>) -> Fut + Send + Sync + 'static,
+    F: Fn(Vec<S>) -> Fut + Send + Sync + 'static,
     Fut: Future<Output = ()> + Send + 'static,
 {
-    fn handle(self: Arc<Self>, snapshot: Vec<AccountSnapshot>) -> BoxFuture<'static, ()>
+    fn handle(self: Arc<Self>, snapshot: Vec<S>) -> BoxFuture<'static, ()>
     where
-        AccountSnapshot: Send + 'static,
+        S: Send + 'static,
     {
         Box::pin(async move { self(snapshot).await })
     }
 }
 
-pub struct TransactionDispatcher<L, H> {
+/// Follows a [`TransactionDispatcher`] run, e.g. to journal or count the transactions.
+pub trait TransactionObserver<A: Aggregate> {
+    /// Called with every transaction once the ledger applied or rejected it.
+    fn observe<'a>(&'a self, event: &'a A::EventData, accepted: bool) -> BoxFuture<'a, ()>;
+
+    /// Called with every applied transaction a rule flagged, once per rule, after
+    /// [`observe`](Self::observe).
+    fn flag<'a>(&'a self, _event: &'a A::EventData, _rule: &'a str) -> BoxFuture<'a, ()> {
+        Box::pin(async {})
+    }
+
+    /// Called once all transactions are applied, with the final snapshots.
+    fn finish<'a>(&'a self, snapshots: &'a [A::Snapshot]) -> BoxFuture<'a, ()>;
+}
+
+type Observer<A> = Arc<dyn TransactionObserver<A> + Send + Sync>;
+
+type RejectionHandler<A> = Arc<dyn ErrorHandler<Rejection<A>> + Send + Sync>;
+
+/// Applies every event of type `A::EventData` to the aggregates of type `A` in a ledger,
+/// then hands the snapshots to a [`SnapshotHandler`].
+pub struct TransactionDispatcher<A: Aggregate, L, H> {
     ledger: Arc<L>,
     handler: Arc<H>,
+    observers: Vec<Observer<A>>,
+    rejection_handler: Option<RejectionHandler<A>>,
+    /// Whether the dispatcher handed the final snapshots off, once it is done.
+    completed: (watch::Sender<Option<bool>>, watch::Receiver<Option<bool>>),
 }
 
-impl<A, H> TransactionDispatcher<InMemoryLedger<A>, H>
+/// Completes once a [`TransactionDispatcher`] is done, see
+/// [`TransactionDispatcher::completion`].
+#[derive(Clone)]
+pub struct Completion(watch::Receiver<Option<bool>>);
+
+impl Completion {
+    /// Waits until the dispatcher is done and returns whether it handed the final snapshots
+    /// to its snapshot handler and observers. Returns `false` if the snapshots could not be
+    /// read from the ledger, or the dispatcher was dropped before it was done.
+    pub async fn wait(mut self) -> bool {
+        loop {
+            if let Some(handed_off) = *self.0.borrow() {
+                return handed_off;
+            }
+            if self.0.changed().await.is_err() {
+                return false;
+            }
+        }
+    }
+}
+
+impl<A, H> TransactionDispatcher<A, InMemoryLedger<A>, H>
 where
     A: Aggregate + Clone + Send + Sync + 'static,
-    H: SnapshotHandler + Send + Sync + 'static,
+    H: SnapshotHandler<A::Snapshot> + Send + Sync + 'static,
 {
     pub fn new(handler: H) -> Self {
+        Self::with_ledger(InMemoryLedger::new(), handler)
+    }
+}
+
+impl<A, L, H> TransactionDispatcher<A, L, H>
+where
+    A: Aggregate,
+    H: SnapshotHandler<A::Snapshot> + Send + Sync + 'static,
+{
+    /// Creates a dispatcher that applies the transactions to an existing `ledger`.
+    pub fn with_ledger(ledger: Arc<L>, handler: H) -> Self {
         Self {
-            ledger: InMemoryLedger::new(),
+            ledger,
             handler: Arc::new(handler),
+            observers: Vec::new(),
+            rejection_handler: None,
+            completed: watch::channel(None),
+        }
+    }
+
+    /// Lets `observer` follow every transaction, and the snapshots they result in.
+    ///
+    /// Observers are called in the order they were added.
+    pub fn observer(mut self, observer: Observer<A>) -> Self {
+        self.observers.push(observer);
+        self
+    }
+
+    /// Hands transactions the ledger rejected to `rejection_handler` instead of logging them.
+    pub fn rejection_handler(mut self, rejection_handler: RejectionHandler<A>) -> Self {
+        self.rejection_handler = Some(rejection_handler);
+        self
+    }
+
+    /// Returns a signal that completes once the dispatcher handled every transaction, even
+    /// after it was handed to a [`Dispatcher`] that runs it in the background.
+    pub fn completion(&self) -> Completion {
+        Completion(self.completed.1.clone())
+    }
+}
+
+impl<A, L, H> TransactionDispatcher<A, L, H>
+where
+    A: Aggregate,
+    A::ID: Display,
+    A::Error: Display,
+{
+    async fn report(
+        &self,
+        id: A::ID,
+        tx_id: A::TxID,
+        event: Option<A::EventData>,
+        error: StoreError<A::ID, A::Error>,
+    ) {
+        match (error, &self.rejection_handler, event) {
+            (StoreError::Rejected(error), Some(handler), Some(event)) => {
+                let rejection = Rejection {
+                    id,
+                    tx_id,
+                    event,
+                    error,
+                };
+                Arc::clone(handler).handle_error(rejection).await;
+            }
+            (StoreError::Rejected(error), ..) => {
+                eprintln!("Error processing transaction: {error}")
+            }
+            (error, ..) => eprintln!("Failed to process transaction: {error}"),
         }
     }
 }
 
-impl<H> DispatcherHandler<TransactionEvent> for TransactionDispatcher<InMemoryLedger<Account>, H>
+impl<A, L, H> TransactionDispatcher<A, L, H>
 where
-    H: SnapshotHandler + Send + Sync + 'static,
+    A: Aggregate + Send + Sync + 'static,
+    A::ID: Display,
+    A::EventData: Route<A::ID, A::TxID> + Clone + 'static,
+    A::Snapshot: 'static,
+    A::Error: Display + Send,
+    L: Ledger<A> + Send + Sync + 'static,
+    H: SnapshotHandler<A::Snapshot> + Send + Sync + 'static,
 {
-    fn handle(self, updates: DispatcherHandlerRx<TransactionEvent>) -> BoxFuture<'static, ()>
+    /// Applies every update until all senders of `updates` are dropped, hands the final
+    /// snapshots to the snapshot handler and observers, and returns them.
+    ///
+    /// Returns `None` if the snapshots could not be read from the ledger.
+    pub async fn run(self, updates: DispatcherHandlerRx<A::EventData>) -> Option<Vec<A::Snapshot>>
     where
-        UpdateWithCx<TransactionEvent>: Send + 'static,
+        A::Snapshot: Clone,
     {
-        let this = Arc::new(self);
-        let other = Arc::clone(&this);
+        Arc::new(self).dispatch(updates, Vec::clone).await
+    }
+
+    /// Applies every update and hands the final snapshots to the snapshot handler and
+    /// observers, keeping what `keep` takes from them before.
+    async fn dispatch<T>(
+        self: Arc<Self>,
+        updates: DispatcherHandlerRx<A::EventData>,
+        keep: impl FnOnce(&Vec<A::Snapshot>) -> T,
+    ) -> Option<T> {
+        let kept = match Arc::clone(&self).apply(updates).await {
+            Some(snapshot) => {
+                let kept = keep(&snapshot);
+                Arc::clone(&self.handler).handle(snapshot).await;
+                Some(kept)
+            }
+            None => None,
+        };
+        // The dispatcher keeps a receiver itself, so this cannot fail.
+        let _ = self.completed.0.send(Some(kept.is_some()));
+        kept
+    }
+
+    /// Applies every update, then reads the snapshots and hands them to the observers.
+    async fn apply(
+        self: Arc<Self>,
+        updates: DispatcherHandlerRx<A::EventData>,
+    ) -> Option<Vec<A::Snapshot>> {
+        let this = Arc::clone(&self);
         UnboundedReceiverStream::new(updates)
             .for_each(move |cx| {
-                let ledger = Arc::clone(&this.ledger);
+                let this = Arc::clone(&this);
                 async move {
-                    if (Arc::clone(&ledger)
-                        .process_transaction(cx.update.client_id, cx.update.tx_id, cx.update)
-                        .await)
-                        .is_err()
-                    {
-                        eprintln!("failed to process event")
+                    let (update, reply) = cx.into_parts();
+                    let (id, tx_id) = update.route();
+                    let keep_event = !this.observers.is_empty() || this.rejection_handler.is_some();
+                    let event = keep_event.then(|| update.clone());
+                    let result = Arc::clone(&this.ledger)
+                        .process_transaction(id.clone(), tx_id.clone(), update)
+                        .await;
+                    if let Some(event) = &event {
+                        for observer in &this.observers {
+                            observer.observe(event, result.is_ok()).await;
+                        }
                     }
-                }
-            })
-            .then(move |()| {
-                let this = Arc::clone(&other);
-                async move {
-                    if let Ok(snapshot) = Arc::clone(&this.ledger).all_snapshots().await {
-                        Arc::clone(&this.handler).handle(snapshot).await;
+                    let outcome = match result {
+                        Ok(applied) => {
+                            for rule in &applied.flags {
+                                eprintln!("Transaction of `{id}` was flagged by rule `{rule}`");
+                                if let Some(event) = &event {
+                                    for observer in &this.observers {
+                                        observer.flag(event, rule).await;
+                                    }
+                                }
+                            }
+                            Ok(())
+                        }
+                        Err(error) => {
+                            let reason = reply.as_ref().map(|_| error.to_string());
+                            this.report(id, tx_id, event, error).await;
+                            Err(reason.unwrap_or_default())
+                        }
+                    };
+                    if let Some(reply) = reply {
+                        let _ = reply.send(outcome);
                     }
                 }
             })
-            .boxed()
+            .await;
+
+        let snapshot = match Arc::clone(&self.ledger).all_snapshots().await {
+            Ok(snapshot) => snapshot,
+            Err(error) => {
+                eprintln!("Failed to read account snapshots: {error}");
+                return None;
+            }
+        };
+        for observer in &self.observers {
+            observer.finish(&snapshot).await;
+        }
+        Some(snapshot)
+    }
+}
+
+impl<A, L, H> DispatcherHandler<A::EventData> for TransactionDispatcher<A, L, H>
+where
+    A: Aggregate + Send + Sync + 'static,
+    A::ID: Display,
+    A::EventData: Route<A::ID, A::TxID> + Clone + 'static,
+    A::Snapshot: 'static,
+    A::Error: Display + Send,
+    L: Ledger<A> + Send + Sync + 'static,
+    H: SnapshotHandler<A::Snapshot> + Send + Sync + 'static,
+{
+    fn handle(self, updates: DispatcherHandlerRx<A::EventData>) -> BoxFuture<'static, ()>
+    where
+        UpdateWithCx<A::EventData>: Send + 'static,
+    {
+        async move {
+            Arc::new(self).dispatch(updates, |_| ()).await;
+        }
+        .boxed()
+    }
+}
+
+/// Wires a listener, a ledger, an error handler and a snapshot handler into a run.
+///
+/// ```no_run
+/// # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+/// use leviathan::{
+///     engine::ledger::{Account, InMemoryLedger},
+///     listener::polling,
+///     output::{OutputFormat, SnapshotOrder, SnapshotWriter},
+///     Pipeline,
+/// };
+/// use tokio_util::sync::CancellationToken;
+///
+/// let ledger = InMemoryLedger::<Account>::new();
+/// let writer = SnapshotWriter::stdout(OutputFormat::Csv, SnapshotOrder::Client);
+/// let shutdown = CancellationToken::new();
+/// Pipeline::new(ledger, writer.clone())
+///     .run(polling("transactions.csv").await?, shutdown)
+///     .await;
+/// writer.check().await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Pipeline<A: Aggregate, L, H, Eh: ?Sized> {
+    dispatcher: TransactionDispatcher<A, L, H>,
+    error_handler: Arc<Eh>,
+}
+
+impl<A, L, H> Pipeline<A, L, H, LoggingErrorHandler>
+where
+    A: Aggregate,
+    H: SnapshotHandler<A::Snapshot> + Send + Sync + 'static,
+{
+    /// Creates a pipeline that applies the updates to `ledger` and logs listener errors.
+    pub fn new(ledger: Arc<L>, handler: H) -> Self {
+        Self {
+            dispatcher: TransactionDispatcher::with_ledger(ledger, handler),
+            error_handler: LoggingErrorHandler::with_custom_text(
+                "An error from the update listener",
+            ),
+        }
+    }
+}
+
+impl<A, L, H, Eh> Pipeline<A, L, H, Eh>
+where
+    A: Aggregate + Send + Sync + 'static,
+    A::ID: Display,
+    A::EventData: Route<A::ID, A::TxID> + Clone + 'static,
+    A::Snapshot: 'static,
+    A::Error: Display + Send,
+    L: Ledger<A> + Send + Sync + 'static,
+    H: SnapshotHandler<A::Snapshot> + Send + Sync + 'static,
+    Eh: ?Sized,
+{
+    /// Hands the errors of the update listener to `error_handler`.
+    pub fn error_handler<Eh2>(self, error_handler: Arc<Eh2>) -> Pipeline<A, L, H, Eh2>
+    where
+        Eh2: ?Sized,
+    {
+        Pipeline {
+            dispatcher: self.dispatcher,
+            error_handler,
+        }
+    }
+
+    /// See [`TransactionDispatcher::observer`].
+    pub fn observer(mut self, observer: Observer<A>) -> Self {
+        self.dispatcher = self.dispatcher.observer(observer);
+        self
+    }
+
+    /// See [`TransactionDispatcher::rejection_handler`].
+    pub fn rejection_handler(mut self, rejection_handler: RejectionHandler<A>) -> Self {
+        self.dispatcher = self.dispatcher.rejection_handler(rejection_handler);
+        self
+    }
+
+    /// Applies every update from `listener`, then hands the snapshots to the snapshot handler.
+    ///
+    /// Once `shutdown` is cancelled, the pipeline stops taking updates from `listener`,
+    /// applies the ones it already took and hands off the snapshots as usual.
+    pub async fn run<Lst, Item, ListenerErr>(self, listener: Lst, shutdown: CancellationToken)
+    where
+        Lst: UpdateListener<Item, ListenerErr> + Send,
+        Item: Into<UpdateWithCx<A::EventData>>,
+        ListenerErr: Debug,
+        Eh: ErrorHandler<ListenerErr>,
+    {
+        Dispatcher::new()
+            .messages_handler(self.dispatcher)
+            .dispatch_with_listener(listener, self.error_handler, shutdown)
+            .await;
     }
 }
 
 pub async fn pipeline<'a, L, ListenerErr, H, Fut>(listener: L, handler: H)
 where
-    L: UpdateListener<ListenerErr> + Send + 'a,
+    L: UpdateListener<TransactionEvent, ListenerErr> + Send + 'a,
     ListenerErr: Debug,
     H: Fn(Vec<AccountSnapshot>) -> Fut + Send + Sync + 'static,
     Fut: Future<Output = ()> + Send + 'static,
 {
-    Dispatcher::new()
-        .messages_handler(TransactionDispatcher::new(handler))
-        .dispatch_with_listener(
-            listener,
-            LoggingErrorHandler::with_custom_text("An error from the update listener"),
-        )
+    Pipeline::new(InMemoryLedger::<Account>::new(), handler)
+        .run(listener, CancellationToken::new())
         .await;
 }
 
-/// Handler function that writes the account snapshots as a CSV to stdout.
-pub async fn to_std_out(snapshot: Vec<AccountSnapshot>) {
-    let mut wri = csv_async::AsyncWriterBuilder::new()
-        .has_headers(true)
-        .create_serializer(io::stdout());
-
-    for data in snapshot {
-        wri.serialize(&data).await.unwrap();
-    }
+/// Writes the account snapshots as a CSV to stdout. See [`SnapshotWriter`](output::SnapshotWriter) for a
+/// [`SnapshotHandler`] that does the same.
+pub async fn to_std_out(snapshot: Vec<AccountSnapshot>) -> io::Result<()> {
+    write_snapshots(io::stdout(), OutputFormat::Csv, &snapshot).await
 }
@@ -0,0 +1,100 @@
+use std::{io, path::PathBuf};
+
+use thiserror::Error;
+
+/// An error raised while setting up a listener, before any update is produced.
+#[derive(Debug, Error)]
+pub enum SetupError {
+    #[error("Input `{}` does not exist", path.display())]
+    NotFound { path: PathBuf },
+    #[error("Permission denied while opening input `{}`", path.display())]
+    PermissionDenied { path: PathBuf },
+    #[error("Failed to open input `{}`: {source}", path.display())]
+    Io { path: PathBuf, source: io::Error },
+    #[error("Input `{}` is not readable transaction data: {reason}", path.display())]
+    InvalidData { path: PathBuf, reason: String },
+}
+
+impl SetupError {
+    pub(crate) fn open(path: PathBuf, source: io::Error) -> Self {
+        match source.kind() {
+            io::ErrorKind::NotFound => Self::NotFound { path },
+            io::ErrorKind::PermissionDenied => Self::PermissionDenied { path },
+            _ => Self::Io { path, source },
+        }
+    }
+}
+
+/// Why a record could not be decoded.
+#[derive(Debug, Error)]
+pub enum DecodeError {
+    #[error(transparent)]
+    Csv(#[from] csv_async::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// A record that the listener failed to turn into an update.
+#[derive(Debug, Error)]
+#[error("Failed to read record from `{source_name}` at line {line:?}: {error}")]
+pub struct RecordError {
+    /// Name of the input the record was read from, e.g. the file path.
+    pub source_name: String,
+    /// Line on which the record starts, if known.
+    pub line: Option<u64>,
+    /// Byte offset at which the record starts, if known.
+    pub byte: Option<u64>,
+    /// The raw fields of the record, if it could be read at all.
+    pub record: Option<Vec<String>>,
+    /// The underlying decoding error.
+    #[source]
+    pub error: DecodeError,
+}
+
+impl RecordError {
+    pub(crate) fn from_csv(
+        source_name: &str,
+        error: csv_async::Error,
+        record: Option<&csv_async::StringRecord>,
+    ) -> Self {
+        let position = record
+            .and_then(|record| record.position())
+            .or_else(|| error.position())
+            .cloned();
+        Self {
+            source_name: source_name.to_owned(),
+            line: position.as_ref().map(|pos| pos.line()),
+            byte: position.as_ref().map(|pos| pos.byte()),
+            record: record.map(|record| record.iter().map(str::to_owned).collect()),
+            error: error.into(),
+        }
+    }
+
+    /// Whether the input could not be read any further, rather than this record decoded.
+    pub fn is_fatal(&self) -> bool {
+        match &self.error {
+            DecodeError::Csv(error) => error.is_io_error(),
+            DecodeError::Json(_) => false,
+            DecodeError::Io(_) => true,
+        }
+    }
+
+    /// Creates the error for a record of line-based input, starting at `byte`.
+    pub(crate) fn at_line(
+        source_name: &str,
+        error: impl Into<DecodeError>,
+        line: u64,
+        byte: u64,
+        record: Option<String>,
+    ) -> Self {
+        Self {
+            source_name: source_name.to_owned(),
+            line: Some(line),
+            byte: Some(byte),
+            record: record.map(|record| vec![record]),
+            error: error.into(),
+        }
+    }
+}
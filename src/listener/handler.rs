@@ -5,9 +5,9 @@ use tokio::{
     sync::{mpsc, mpsc::UnboundedReceiver},
     task::JoinHandle,
 };
+use tokio_util::sync::CancellationToken;
 
 use crate::{
-    engine::domain::TransactionEvent,
     error_handler::ErrorHandler,
     listener::{update::UpdateWithCx, UpdateListener},
 };
@@ -35,18 +35,24 @@ where
     }
 }
 
-pub struct Dispatcher {
-    messages_queue: Tx<TransactionEvent>,
+pub struct Dispatcher<Upd> {
+    messages_queue: Tx<Upd>,
     running_handlers: FuturesUnordered<JoinHandle<()>>,
 }
 
-impl Default for Dispatcher {
+impl<Upd> Default for Dispatcher<Upd>
+where
+    Upd: Send + 'static,
+{
     fn default() -> Self {
         Self::new()
     }
 }
 
-impl Dispatcher {
+impl<Upd> Dispatcher<Upd>
+where
+    Upd: Send + 'static,
+{
     pub fn new() -> Self {
         Self {
             messages_queue: None,
@@ -54,10 +60,9 @@ impl Dispatcher {
         }
     }
 
-    fn new_tx<H, Upd>(&mut self, h: H) -> Tx<Upd>
+    fn new_tx<H>(&mut self, h: H) -> Tx<Upd>
     where
         H: DispatcherHandler<Upd> + Send + 'static,
-        Upd: Send + 'static,
     {
         let (tx, rx) = mpsc::unbounded_channel();
         let join_handle = tokio::spawn(h.handle(rx));
@@ -69,23 +74,32 @@ impl Dispatcher {
 
     pub fn messages_handler<H>(mut self, h: H) -> Self
     where
-        H: DispatcherHandler<TransactionEvent> + 'static + Send,
+        H: DispatcherHandler<Upd> + 'static + Send,
     {
         self.messages_queue = self.new_tx(h);
         self
     }
 
-    pub async fn dispatch_with_listener<'a, UListener, ListenerE, Eh>(
+    /// Sends every update from `update_listener` to the handler, then waits for it to finish.
+    ///
+    /// Once `shutdown` is cancelled, no more updates are taken from the listener, but the
+    /// handler still finishes the ones it was sent.
+    ///
+    /// Updates may be anything that converts into an [`UpdateWithCx`], so that listeners
+    /// can attach a [`Reply`](crate::listener::update::Reply) to them.
+    pub async fn dispatch_with_listener<'a, UListener, Item, ListenerE, Eh>(
         &'a mut self,
         mut update_listener: UListener,
         update_listener_error_handler: Arc<Eh>,
+        shutdown: CancellationToken,
     ) where
-        UListener: UpdateListener<ListenerE> + 'a,
-        Eh: ErrorHandler<ListenerE> + 'a,
+        UListener: UpdateListener<Item, ListenerE> + 'a,
+        Item: Into<UpdateWithCx<Upd>>,
+        Eh: ErrorHandler<ListenerE> + ?Sized + 'a,
         ListenerE: Debug,
     {
         {
-            let stream = update_listener.as_stream();
+            let stream = update_listener.as_stream().take_until(shutdown.cancelled());
             tokio::pin!(stream);
             while let Some(upd) = stream.next().await {
                 self.process_update(upd, &update_listener_error_handler)
@@ -95,12 +109,13 @@ impl Dispatcher {
         self.wait_for_handlers().await;
     }
 
-    async fn process_update<ListenerE, Eh>(
+    async fn process_update<Item, ListenerE, Eh>(
         &self,
-        update: Result<TransactionEvent, ListenerE>,
+        update: Result<Item, ListenerE>,
         update_listener_error_handler: &Arc<Eh>,
     ) where
-        Eh: ErrorHandler<ListenerE>,
+        Item: Into<UpdateWithCx<Upd>>,
+        Eh: ErrorHandler<ListenerE> + ?Sized,
         ListenerE: Debug,
     {
         {
@@ -114,7 +129,7 @@ impl Dispatcher {
                 }
             };
 
-            send(&self.messages_queue, update)
+            send(&self.messages_queue, update.into())
         }
     }
 
@@ -125,12 +140,29 @@ impl Dispatcher {
     }
 }
 
-fn send<Upd>(tx: &Tx<Upd>, update: Upd)
-where
-    Upd: Debug,
-{
+/// Completes once the process is asked to stop with SIGINT (Ctrl-C) or, on Unix, SIGTERM.
+pub async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = terminate.recv() => {}
+                }
+                return;
+            }
+            Err(error) => eprintln!("Failed to listen for SIGTERM: {error}"),
+        }
+    }
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+fn send<Upd>(tx: &Tx<Upd>, update: UpdateWithCx<Upd>) {
     if let Some(tx) = tx {
-        if let Err(error) = tx.send(UpdateWithCx { update }) {
+        if let Err(error) = tx.send(update) {
             eprintln!(
                 "The RX part of the channel is closed, but an update is received.\nError:{}\n",
                 error
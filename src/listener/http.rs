@@ -0,0 +1,263 @@
+use std::{convert::Infallible, future::Future, io::Cursor, net::SocketAddr};
+
+use futures::StreamExt;
+use hyper::{
+    body::Bytes,
+    header,
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, oneshot};
+
+use crate::{
+    engine::domain::TransactionEvent,
+    http::{error, response},
+    listener::{csv_reader, receiver, update::UpdateWithCx, AsUpdateStream, UpdateListener},
+};
+
+type Sender = mpsc::UnboundedSender<Result<UpdateWithCx<TransactionEvent>, Infallible>>;
+
+/// Starts a server on `addr` that accepts transactions with `POST /transactions`, and
+/// returns the address it listens on and a listener of the posted transactions.
+///
+/// The body is either a CSV file with the same columns as the input files (`text/csv`),
+/// or a transaction or array of transactions in JSON (`application/json`). The response
+/// lists, in order, whether each transaction was accepted, rejected or could not be
+/// decoded, so the request only completes once the dispatcher handled every transaction.
+///
+/// The listener ends once `shutdown` completes and the pending requests are answered.
+pub fn http_listener<F>(
+    addr: &SocketAddr,
+    shutdown: F,
+) -> hyper::Result<(
+    SocketAddr,
+    impl UpdateListener<UpdateWithCx<TransactionEvent>, Infallible>,
+)>
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    let (tx, rx) = mpsc::unbounded_channel();
+    let make_service = make_service_fn(move |_| {
+        let tx = tx.clone();
+        async move { Ok::<_, Infallible>(service_fn(move |request| ingest(tx.clone(), request))) }
+    });
+    let server = Server::try_bind(addr)?.serve(make_service);
+    let local_addr = server.local_addr();
+    let server = server.with_graceful_shutdown(shutdown);
+    tokio::spawn(async move {
+        if let Err(err) = server.await {
+            eprintln!("The HTTP listener failed: {err}");
+        }
+    });
+
+    Ok((local_addr, receiver(rx)))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum JsonBody {
+    One(TransactionEvent),
+    Many(Vec<TransactionEvent>),
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum Status {
+    Accepted,
+    Rejected,
+    Invalid,
+}
+
+/// The result of one transaction of a request.
+#[derive(Debug, Serialize)]
+struct Outcome {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    client: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tx: Option<u32>,
+    status: Status,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
+}
+
+enum Pending {
+    Sent(u16, u32, oneshot::Receiver<Result<(), String>>),
+    Invalid(String),
+}
+
+async fn ingest(tx: Sender, request: Request<Body>) -> Result<Response<Body>, Infallible> {
+    if request.uri().path() != "/transactions" {
+        return Ok(error(StatusCode::NOT_FOUND, "Not found"));
+    }
+    if request.method() != Method::POST {
+        return Ok(error(
+            StatusCode::METHOD_NOT_ALLOWED,
+            "Only POST is supported",
+        ));
+    }
+    let content_type = request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_owned();
+    let body = match hyper::body::to_bytes(request.into_body()).await {
+        Ok(body) => body,
+        Err(err) => return Ok(error(StatusCode::BAD_REQUEST, err)),
+    };
+    let records = if content_type.starts_with("application/json") {
+        match serde_json::from_slice(&body) {
+            Ok(JsonBody::One(event)) => vec![Ok(event)],
+            Ok(JsonBody::Many(events)) => events.into_iter().map(Ok).collect(),
+            Err(err) => return Ok(error(StatusCode::BAD_REQUEST, err)),
+        }
+    } else if content_type.starts_with("text/csv") {
+        decode_csv(body).await
+    } else {
+        return Ok(error(
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "Expected `application/json` or `text/csv`",
+        ));
+    };
+
+    let mut pending = Vec::with_capacity(records.len());
+    for record in records {
+        match record {
+            Ok(event) => {
+                let (reply, result) = oneshot::channel();
+                let (client, tx_id) = (event.client_id, event.tx_id);
+                if tx.send(Ok(UpdateWithCx::with_reply(event, reply))).is_err() {
+                    return Ok(error(
+                        StatusCode::SERVICE_UNAVAILABLE,
+                        "The engine is shutting down",
+                    ));
+                }
+                pending.push(Pending::Sent(client, tx_id, result));
+            }
+            Err(reason) => pending.push(Pending::Invalid(reason)),
+        }
+    }
+
+    let mut outcomes = Vec::with_capacity(pending.len());
+    for pending in pending {
+        outcomes.push(match pending {
+            Pending::Sent(client, tx_id, result) => {
+                let (status, reason) = match result.await {
+                    Ok(Ok(())) => (Status::Accepted, None),
+                    Ok(Err(reason)) => (Status::Rejected, Some(reason)),
+                    Err(_) => (
+                        Status::Rejected,
+                        Some("The engine stopped before applying the transaction".to_owned()),
+                    ),
+                };
+                Outcome {
+                    client: Some(client),
+                    tx: Some(tx_id),
+                    status,
+                    reason,
+                }
+            }
+            Pending::Invalid(reason) => Outcome {
+                client: None,
+                tx: None,
+                status: Status::Invalid,
+                reason: Some(reason),
+            },
+        });
+    }
+    Ok(match serde_json::to_vec(&outcomes) {
+        Ok(body) => response(StatusCode::OK, body),
+        Err(err) => error(StatusCode::INTERNAL_SERVER_ERROR, err),
+    })
+}
+
+async fn decode_csv(body: Bytes) -> Vec<Result<TransactionEvent, String>> {
+    let mut listener = csv_reader("request", Cursor::new(body));
+    listener
+        .as_stream()
+        .map(|record| record.map_err(|err| err.to_string()))
+        .collect()
+        .await
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpStream,
+    };
+    use tokio_util::sync::CancellationToken;
+
+    use super::*;
+    use crate::{
+        engine::{
+            domain::AccountSnapshot,
+            ledger::{Account, InMemoryLedger, Ledger},
+        },
+        listener::handler::Dispatcher,
+        TransactionDispatcher,
+    };
+
+    async fn post(addr: SocketAddr, content_type: &str, body: &str) -> serde_json::Value {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let request = format!(
+            "POST /transactions HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\
+             Content-Type: {content_type}\r\nContent-Length: {}\r\n\r\n{body}",
+            body.len()
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        let (_, body) = response.split_once("\r\n\r\n").unwrap();
+        serde_json::from_str(body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_http_listener() {
+        let (stop, stopped) = oneshot::channel::<()>();
+        let (addr, listener) = http_listener(&([127, 0, 0, 1], 0).into(), async {
+            let _ = stopped.await;
+        })
+        .unwrap();
+        let ledger = InMemoryLedger::<Account>::new();
+        let dispatcher = TransactionDispatcher::with_ledger(
+            Arc::clone(&ledger),
+            |_: Vec<AccountSnapshot>| async {},
+        );
+        let engine = tokio::spawn(async move {
+            Dispatcher::new()
+                .messages_handler(dispatcher)
+                .dispatch_with_listener(
+                    listener,
+                    Arc::new(|_: Infallible| async {}),
+                    CancellationToken::new(),
+                )
+                .await;
+        });
+
+        let body = r#"[
+            {"type": "deposit", "client": 1, "tx": 1, "amount": "10"},
+            {"type": "withdrawal", "client": 1, "tx": 2, "amount": "25"}
+        ]"#;
+        let outcomes = post(addr, "application/json", body).await;
+        assert_eq!(outcomes[0]["status"], "accepted");
+        assert_eq!(outcomes[1]["status"], "rejected");
+        assert_eq!(outcomes[1]["tx"], 2);
+        assert!(outcomes[1]["reason"]
+            .as_str()
+            .unwrap()
+            .contains("sufficient funds"));
+
+        let body = "type,client,tx,amount\nwithdrawal,1,3,4\nrefund,1,4,1\n";
+        let outcomes = post(addr, "text/csv", body).await;
+        assert_eq!(outcomes[0]["status"], "accepted");
+        assert_eq!(outcomes[1]["status"], "invalid");
+
+        stop.send(()).unwrap();
+        engine.await.unwrap();
+        assert_eq!(ledger.snapshot(1).await.unwrap().total, 6.into());
+    }
+}
@@ -1,17 +1,31 @@
+pub mod error;
 pub mod handler;
+#[cfg(feature = "http")]
+pub mod http;
+pub mod ndjson;
 pub mod update;
 
-use std::path::Path;
+use std::{fmt, path::Path, str::FromStr};
 
-use futures::Stream;
-use tokio::{fs::File, io};
+use futures::{future::Either, stream, Stream, StreamExt};
+use tokio::{
+    fs::File,
+    io::{self, AsyncBufReadExt, BufReader},
+    sync::mpsc,
+};
 
-use crate::engine::domain::TransactionEvent;
+use crate::{
+    engine::domain::TransactionEvent,
+    listener::{
+        error::{RecordError, SetupError},
+        ndjson::ndjson_reader,
+    },
+};
 
-pub trait UpdateListener<E>: for<'a> AsUpdateStream<'a, E> {}
+pub trait UpdateListener<Upd, E>: for<'a> AsUpdateStream<'a, Upd, E> {}
 
-pub trait AsUpdateStream<'a, E> {
-    type Stream: Stream<Item = Result<TransactionEvent, E>> + Send + 'a;
+pub trait AsUpdateStream<'a, Upd, E> {
+    type Stream: Stream<Item = Result<Upd, E>> + Send + 'a;
 
     /// Creates the update [`Stream`].
     ///
@@ -35,12 +49,12 @@ impl<St, Assf> StatefulListener<St, Assf> {
     }
 }
 
-impl<'a, St, Assf, Strm, E> AsUpdateStream<'a, E> for StatefulListener<St, Assf>
+impl<'a, St, Assf, Strm, Upd, E> AsUpdateStream<'a, Upd, E> for StatefulListener<St, Assf>
 where
     (St, Strm): 'a,
     Strm: Send,
     Assf: FnMut(&'a mut St) -> Strm,
-    Strm: Stream<Item = Result<TransactionEvent, E>>,
+    Strm: Stream<Item = Result<Upd, E>>,
 {
     type Stream = Strm;
 
@@ -49,36 +63,394 @@ where
     }
 }
 
-impl<St, Assf, E> UpdateListener<E> for StatefulListener<St, Assf> where
-    Self: for<'a> AsUpdateStream<'a, E>
+impl<St, Assf, Upd, E> UpdateListener<Upd, E> for StatefulListener<St, Assf> where
+    Self: for<'a> AsUpdateStream<'a, Upd, E>
 {
 }
 
-pub async fn polling<T>(filename: T) -> impl UpdateListener<csv_async::Error>
+/// Wraps `listener` so that it ends after `limit` updates.
+pub fn take<L, Upd, E>(listener: L, limit: usize) -> impl UpdateListener<Upd, E>
+where
+    L: UpdateListener<Upd, E> + Send + 'static,
+{
+    fn stream<L, Upd, E>(
+        (listener, limit): &mut (L, usize),
+    ) -> impl Stream<Item = Result<Upd, E>> + Send + '_
+    where
+        L: UpdateListener<Upd, E>,
+    {
+        listener.as_stream().take(*limit)
+    }
+
+    StatefulListener::new((listener, limit), stream)
+}
+
+/// Wraps `listener` so that it drops its first `count` updates.
+pub fn skip<L, Upd, E>(listener: L, count: usize) -> impl UpdateListener<Upd, E>
+where
+    L: UpdateListener<Upd, E> + Send + 'static,
+{
+    fn stream<L, Upd, E>(
+        (listener, count): &mut (L, usize),
+    ) -> impl Stream<Item = Result<Upd, E>> + Send + '_
+    where
+        L: UpdateListener<Upd, E>,
+    {
+        listener.as_stream().skip(*count)
+    }
+
+    StatefulListener::new((listener, count), stream)
+}
+
+/// Wraps `listener` so that it yields the updates of `left` or `right`, whichever is set.
+pub fn either<L, R, Upd, E>(listener: Either<L, R>) -> impl UpdateListener<Upd, E>
+where
+    L: UpdateListener<Upd, E> + Send + 'static,
+    R: UpdateListener<Upd, E> + Send + 'static,
+{
+    fn stream<L, R, Upd, E>(
+        listener: &mut Either<L, R>,
+    ) -> impl Stream<Item = Result<Upd, E>> + Send + '_
+    where
+        L: UpdateListener<Upd, E>,
+        R: UpdateListener<Upd, E>,
+    {
+        match listener {
+            Either::Left(listener) => Either::Left(listener.as_stream()),
+            Either::Right(listener) => Either::Right(listener.as_stream()),
+        }
+    }
+
+    StatefulListener::new(listener, stream)
+}
+
+/// Creates a listener over the updates sent through `rx`, ending once every sender is dropped.
+pub(crate) fn receiver<Upd, E>(
+    rx: mpsc::UnboundedReceiver<Result<Upd, E>>,
+) -> impl UpdateListener<Upd, E>
+where
+    Upd: Send + 'static,
+    E: Send + 'static,
+{
+    fn updates<Upd, E>(
+        rx: &mut mpsc::UnboundedReceiver<Result<Upd, E>>,
+    ) -> impl Stream<Item = Result<Upd, E>> + Send + '_
+    where
+        Upd: Send,
+        E: Send,
+    {
+        stream::poll_fn(move |cx| rx.poll_recv(cx))
+    }
+
+    StatefulListener::new(rx, updates)
+}
+
+/// The encoding of transaction events in an input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputFormat {
+    /// Comma separated values with a `type,client,tx,amount` header.
+    Csv,
+    /// One JSON object per line, with the same field names as the CSV columns.
+    Ndjson,
+}
+
+impl InputFormat {
+    /// Guesses the format from the extension of `path`, if it has a known one.
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "csv" => Some(Self::Csv),
+            "ndjson" | "jsonl" => Some(Self::Ndjson),
+            _ => None,
+        }
+    }
+
+    /// Guesses the format from the first bytes of the input.
+    fn sniff(buffer: &[u8]) -> Self {
+        match buffer.iter().find(|byte| !byte.is_ascii_whitespace()) {
+            Some(b'{') => Self::Ndjson,
+            _ => Self::Csv,
+        }
+    }
+}
+
+impl FromStr for InputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(Self::Csv),
+            "ndjson" | "jsonl" => Ok(Self::Ndjson),
+            _ => Err(format!(
+                "unknown input format `{s}`, expected `csv` or `ndjson`"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for InputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Csv => "csv",
+            Self::Ndjson => "ndjson",
+        })
+    }
+}
+
+/// Opens `filename`, or stdin if it is `-`, and creates a listener over the transaction
+/// events it contains.
+///
+/// Without an explicit `format` it is detected from the file extension, or else from the
+/// first bytes of the input. CSV inputs must have a transaction header.
+pub async fn open_input<T>(
+    filename: T,
+    format: Option<InputFormat>,
+) -> Result<impl UpdateListener<TransactionEvent, RecordError>, SetupError>
+where
+    T: AsRef<Path>,
+{
+    let path = filename.as_ref().to_path_buf();
+    let (source_name, resource) = if path == Path::new("-") {
+        let stdin: Box<dyn io::AsyncRead + Unpin + Send> = Box::new(io::stdin());
+        ("stdin".to_owned(), stdin)
+    } else {
+        let file = File::open(&path)
+            .await
+            .map_err(|err| SetupError::open(path.clone(), err))?;
+        let file: Box<dyn io::AsyncRead + Unpin + Send> = Box::new(file);
+        (path.display().to_string(), file)
+    };
+
+    let mut resource = BufReader::new(resource);
+    let format = match format.or_else(|| InputFormat::from_extension(&path)) {
+        Some(format) => format,
+        None => InputFormat::sniff(
+            resource
+                .fill_buf()
+                .await
+                .map_err(|err| SetupError::open(path.clone(), err))?,
+        ),
+    };
+
+    Ok(either(match format {
+        InputFormat::Csv => Either::Left(csv_with_header(&path, source_name, resource).await?),
+        InputFormat::Ndjson => Either::Right(ndjson_reader(source_name, resource)),
+    }))
+}
+
+/// Column names every CSV input has to provide.
+const REQUIRED_COLUMNS: [&str; 3] = ["type", "client", "tx"];
+
+/// Opens `filename` and creates a listener over the CSV transaction events it contains.
+///
+/// Fails if the file cannot be opened or its header is not a transaction header.
+pub async fn polling<T>(
+    filename: T,
+) -> Result<impl UpdateListener<TransactionEvent, RecordError>, SetupError>
 where
     T: AsRef<Path>,
 {
-    let resource = File::open(filename).await.unwrap();
+    let path = filename.as_ref().to_path_buf();
+    let resource = File::open(&path)
+        .await
+        .map_err(|err| SetupError::open(path.clone(), err))?;
+
+    csv_with_header(&path, path.display().to_string(), resource).await
+}
+
+/// Creates a CSV listener over `resource` after checking that it has a transaction header.
+async fn csv_with_header<R>(
+    path: &Path,
+    source_name: String,
+    resource: R,
+) -> Result<impl UpdateListener<TransactionEvent, RecordError>, SetupError>
+where
+    R: io::AsyncRead + Unpin + Send + 'static,
+{
+    let mut reader = csv_deserializer(resource);
+    let headers = reader.headers().await.map_err(|err| {
+        let reason = err.to_string();
+        match err.into_kind() {
+            csv_async::ErrorKind::Io(source) => SetupError::open(path.to_path_buf(), source),
+            _ => SetupError::InvalidData {
+                path: path.to_path_buf(),
+                reason,
+            },
+        }
+    })?;
+    if let Some(column) = REQUIRED_COLUMNS
+        .iter()
+        .find(|column| !headers.iter().any(|header| header == **column))
+    {
+        return Err(SetupError::InvalidData {
+            path: path.to_path_buf(),
+            reason: format!("missing `{column}` column"),
+        });
+    }
+
+    Ok(csv_listener(source_name, reader))
+}
+
+/// Creates a listener that decodes CSV transaction events from `reader`.
+///
+/// `source_name` is attached to every [`RecordError`] the listener yields.
+pub fn csv_reader<R>(
+    source_name: impl Into<String>,
+    reader: R,
+) -> impl UpdateListener<TransactionEvent, RecordError>
+where
+    R: io::AsyncRead + Unpin + Send + 'static,
+{
+    csv_listener(source_name.into(), csv_deserializer(reader))
+}
+
+fn csv_deserializer<R>(reader: R) -> csv_async::AsyncDeserializer<R>
+where
+    R: io::AsyncRead + Unpin + Send,
+{
+    csv_async::AsyncReaderBuilder::new()
+        .flexible(true)
+        .trim(csv_async::Trim::All)
+        .create_deserializer(reader)
+}
 
+fn csv_listener<R>(
+    source_name: String,
+    reader: csv_async::AsyncDeserializer<R>,
+) -> impl UpdateListener<TransactionEvent, RecordError>
+where
+    R: io::AsyncRead + Unpin + Send + 'static,
+{
     struct State<R: io::AsyncRead + Unpin + Send> {
+        source_name: String,
         reader: csv_async::AsyncDeserializer<R>,
     }
 
     fn stream<T>(
         st: &mut State<T>,
-    ) -> impl Stream<Item = Result<TransactionEvent, csv_async::Error>> + Send + '_
+    ) -> impl Stream<Item = Result<TransactionEvent, RecordError>> + Send + '_
     where
         T: io::AsyncRead + Unpin + Send,
     {
-        st.reader.deserialize::<TransactionEvent>()
+        async_stream::stream! {
+            let headers = match st.reader.headers().await {
+                Ok(headers) => headers.clone(),
+                Err(error) => {
+                    yield Err(RecordError::from_csv(&st.source_name, error, None));
+                    return;
+                }
+            };
+            let mut record = csv_async::StringRecord::new();
+            loop {
+                match st.reader.read_record(&mut record).await {
+                    Ok(true) => {
+                        yield record
+                            .deserialize::<TransactionEvent>(Some(&headers))
+                            .map_err(|error| {
+                                RecordError::from_csv(&st.source_name, error, Some(&record))
+                            });
+                    }
+                    Ok(false) => break,
+                    Err(error) => {
+                        // I/O errors are not recoverable, anything else only affects this record.
+                        let fatal = error.is_io_error();
+                        yield Err(RecordError::from_csv(&st.source_name, error, None));
+                        if fatal {
+                            break;
+                        }
+                    }
+                }
+            }
+        }
     }
 
     let state = State {
-        reader: csv_async::AsyncReaderBuilder::new()
-            .flexible(true)
-            .trim(csv_async::Trim::All)
-            .create_deserializer(resource),
+        source_name,
+        reader,
     };
 
     StatefulListener::new(state, stream)
 }
+
+#[cfg(test)]
+mod test {
+    use futures::StreamExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_record_error_context() {
+        let data = "type,client,tx,amount\ndeposit,1,1,5.0\ndeposit,x,2,1.0\nwithdrawal,1,3,1.0\n";
+        let mut listener = csv_reader("inline.csv", data.as_bytes());
+        let results = listener.as_stream().collect::<Vec<_>>().await;
+
+        assert_eq!(results.len(), 3);
+        assert!(results[0].is_ok());
+        assert!(results[2].is_ok());
+        let error = results[1].as_ref().unwrap_err();
+        assert_eq!(error.source_name, "inline.csv");
+        assert_eq!(error.line, Some(3));
+        assert_eq!(error.byte, Some(38));
+        assert_eq!(
+            error.record,
+            Some(vec![
+                "deposit".to_owned(),
+                "x".to_owned(),
+                "2".to_owned(),
+                "1.0".to_owned()
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_polling_setup_errors() {
+        let missing = std::env::temp_dir().join("leviathan-does-not-exist.csv");
+        assert!(matches!(
+            polling(&missing).await,
+            Err(SetupError::NotFound { path }) if path == missing
+        ));
+
+        let path =
+            std::env::temp_dir().join(format!("leviathan-header-{}.csv", std::process::id()));
+        tokio::fs::write(&path, "kind,client,amount\ndeposit,1,1.0\n")
+            .await
+            .unwrap();
+        let result = polling(&path).await;
+        tokio::fs::remove_file(&path).await.unwrap();
+        assert!(matches!(result, Err(SetupError::InvalidData { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_open_input_detects_format() {
+        let path = std::env::temp_dir().join(format!("leviathan-input-{}", std::process::id()));
+        tokio::fs::write(
+            &path,
+            "\n{\"type\": \"deposit\", \"client\": 1, \"tx\": 1, \"amount\": \"2\"}\n",
+        )
+        .await
+        .unwrap();
+        let mut listener = open_input(&path, None).await.unwrap();
+        let detected = listener.as_stream().collect::<Vec<_>>().await;
+        let forced = open_input(&path, Some(InputFormat::Csv)).await;
+        tokio::fs::remove_file(&path).await.unwrap();
+
+        assert_eq!(detected.len(), 1);
+        assert_eq!(detected[0].as_ref().unwrap().client_id, 1);
+        assert!(matches!(forced, Err(SetupError::InvalidData { .. })));
+        assert_eq!(
+            InputFormat::from_extension(Path::new("events.jsonl")),
+            Some(InputFormat::Ndjson)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_skip_and_take() {
+        let (tx, rx) = mpsc::unbounded_channel::<Result<u32, ()>>();
+        for update in 1..=5 {
+            tx.send(Ok(update)).unwrap();
+        }
+        drop(tx);
+        let mut listener = take(skip(receiver(rx), 2), 2);
+        let updates = listener.as_stream().collect::<Vec<_>>().await;
+        assert_eq!(updates, vec![Ok(3), Ok(4)]);
+    }
+}
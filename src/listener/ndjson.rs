@@ -0,0 +1,205 @@
+use std::{future::Future, net::SocketAddr};
+
+use futures::{Stream, StreamExt};
+use tokio::{
+    io::{self, AsyncBufReadExt, BufReader},
+    net::TcpListener,
+    sync::mpsc,
+};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    engine::domain::TransactionEvent,
+    listener::{error::RecordError, receiver, AsUpdateStream, StatefulListener, UpdateListener},
+};
+
+/// Creates a listener that decodes newline-delimited JSON transaction events from `reader`.
+///
+/// Every line holds one object with the same fields as the CSV columns, e.g.
+/// `{"type": "deposit", "client": 1, "tx": 1, "amount": "2.5"}`. Blank lines are skipped.
+/// `source_name` is attached to every [`RecordError`] the listener yields.
+pub fn ndjson_reader<R>(
+    source_name: impl Into<String>,
+    reader: R,
+) -> impl UpdateListener<TransactionEvent, RecordError>
+where
+    R: io::AsyncRead + Unpin + Send + 'static,
+{
+    struct State<R> {
+        source_name: String,
+        reader: BufReader<R>,
+    }
+
+    fn stream<T>(
+        st: &mut State<T>,
+    ) -> impl Stream<Item = Result<TransactionEvent, RecordError>> + Send + '_
+    where
+        T: io::AsyncRead + Unpin + Send,
+    {
+        async_stream::stream! {
+            let mut line = Vec::new();
+            let (mut number, mut byte) = (0, 0);
+            loop {
+                line.clear();
+                let start = byte;
+                match st.reader.read_until(b'\n', &mut line).await {
+                    Ok(0) => break,
+                    Ok(read) => {
+                        number += 1;
+                        byte += read as u64;
+                        if line.iter().all(u8::is_ascii_whitespace) {
+                            continue;
+                        }
+                        yield serde_json::from_slice::<TransactionEvent>(&line).map_err(|error| {
+                            let record = String::from_utf8_lossy(&line).trim_end().to_owned();
+                            RecordError::at_line(&st.source_name, error, number, start, Some(record))
+                        });
+                    }
+                    Err(error) => {
+                        yield Err(RecordError::at_line(&st.source_name, error, number + 1, start, None));
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    let state = State {
+        source_name: source_name.into(),
+        reader: BufReader::new(reader),
+    };
+
+    StatefulListener::new(state, stream)
+}
+
+/// Accepts TCP connections on `addr` that send newline-delimited JSON transaction events,
+/// see [`ndjson_reader`], and returns the address it listens on and a listener of the
+/// events of every connection.
+///
+/// Once `shutdown` completes, the listener stops accepting connections and reading the open
+/// ones, and ends after the events it already read.
+pub async fn ndjson_tcp<F>(
+    addr: &SocketAddr,
+    shutdown: F,
+) -> io::Result<(
+    SocketAddr,
+    impl UpdateListener<TransactionEvent, RecordError>,
+)>
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    let socket = TcpListener::bind(addr).await?;
+    let local_addr = socket.local_addr()?;
+    let (tx, rx) = mpsc::unbounded_channel();
+    let stopped = CancellationToken::new();
+    tokio::spawn({
+        let stopped = stopped.clone();
+        async move {
+            shutdown.await;
+            stopped.cancel();
+        }
+    });
+    tokio::spawn(async move {
+        loop {
+            let (stream, peer) = tokio::select! {
+                () = stopped.cancelled() => break,
+                accepted = socket.accept() => match accepted {
+                    Ok(accepted) => accepted,
+                    Err(err) => {
+                        eprintln!("Failed to accept a connection: {err}");
+                        continue;
+                    }
+                },
+            };
+            let tx = tx.clone();
+            let stopped = stopped.clone();
+            tokio::spawn(async move {
+                let mut listener = ndjson_reader(peer.to_string(), stream);
+                let updates = listener.as_stream().take_until(stopped.cancelled());
+                tokio::pin!(updates);
+                while let Some(update) = updates.next().await {
+                    if tx.send(update).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+
+    Ok((local_addr, receiver(rx)))
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use rust_decimal_macros::dec;
+    use tokio::{io::AsyncWriteExt, net::TcpStream, sync::oneshot};
+
+    use super::*;
+    use crate::{engine::domain::TransactionType, listener::error::DecodeError};
+
+    #[tokio::test]
+    async fn test_ndjson_reader() {
+        let data = concat!(
+            "{\"type\": \"deposit\", \"client\": 1, \"tx\": 1, \"amount\": \"1.5\"}\n",
+            "\n",
+            "{\"type\": \"refund\", \"client\": 1, \"tx\": 2}\n",
+            "{\"type\": \"dispute\", \"client\": 1, \"tx\": 1}",
+        );
+        let mut listener = ndjson_reader("inline.ndjson", Cursor::new(data));
+        let updates = listener.as_stream().collect::<Vec<_>>().await;
+        assert_eq!(updates.len(), 3);
+
+        let deposit = updates[0].as_ref().unwrap();
+        assert_eq!(deposit.transaction_type, TransactionType::Deposit);
+        assert_eq!(deposit.amount, Some(dec!(1.5)));
+
+        let error = updates[1].as_ref().unwrap_err();
+        assert_eq!(error.line, Some(3));
+        assert_eq!(error.byte, Some(60));
+        assert!(matches!(error.error, DecodeError::Json(_)));
+        assert_eq!(
+            error.record,
+            Some(vec![
+                "{\"type\": \"refund\", \"client\": 1, \"tx\": 2}".to_owned()
+            ])
+        );
+
+        let dispute = updates[2].as_ref().unwrap();
+        assert_eq!(dispute.amount, None);
+    }
+
+    #[tokio::test]
+    async fn test_ndjson_tcp() {
+        let (stop, stopped) = oneshot::channel::<()>();
+        let (addr, mut listener) = ndjson_tcp(&([127, 0, 0, 1], 0).into(), async {
+            let _ = stopped.await;
+        })
+        .await
+        .unwrap();
+
+        let mut connections = Vec::new();
+        for tx in [1, 2] {
+            let mut stream = TcpStream::connect(addr).await.unwrap();
+            let line = format!(
+                "{{\"type\": \"deposit\", \"client\": 3, \"tx\": {tx}, \"amount\": \"1\"}}\n"
+            );
+            stream.write_all(line.as_bytes()).await.unwrap();
+            connections.push(stream);
+        }
+        let updates = listener.as_stream();
+        tokio::pin!(updates);
+        let mut received = Vec::new();
+        for _ in 0..2 {
+            received.push(updates.next().await.unwrap().unwrap().tx_id);
+        }
+        received.sort_unstable();
+        assert_eq!(received, vec![1, 2]);
+
+        // The connections are still open, yet the listener ends.
+        stop.send(()).unwrap();
+        assert!(updates.next().await.is_none());
+        drop(connections);
+    }
+}
@@ -1,4 +1,35 @@
+use tokio::sync::oneshot;
+
+/// Receives whether an update was applied, or why it was not.
+pub type Reply = oneshot::Sender<Result<(), String>>;
+
 #[derive(Debug)]
 pub struct UpdateWithCx<Upd> {
     pub update: Upd,
+    /// Set when the source of the update waits for its result.
+    reply: Option<Reply>,
+}
+
+impl<Upd> UpdateWithCx<Upd> {
+    /// Wraps an `update` whose source waits for its result on `reply`.
+    pub fn with_reply(update: Upd, reply: Reply) -> Self {
+        Self {
+            update,
+            reply: Some(reply),
+        }
+    }
+
+    /// Splits into the update and the reply its source waits on, if any.
+    pub fn into_parts(self) -> (Upd, Option<Reply>) {
+        (self.update, self.reply)
+    }
+}
+
+impl<Upd> From<Upd> for UpdateWithCx<Upd> {
+    fn from(update: Upd) -> Self {
+        Self {
+            update,
+            reply: None,
+        }
+    }
 }
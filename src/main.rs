@@ -1,14 +1,940 @@
-use std::{env, error::Error};
+use std::{
+    fmt::{Debug, Display},
+    future::Future,
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
 
-use leviathan::{listener::polling, pipeline, to_std_out};
+use clap::{Args, Parser, Subcommand};
+use futures::future::{BoxFuture, Either};
+use leviathan::{
+    checkpoint::{Checkpoint, Checkpointer},
+    engine::{
+        bookkeeping::TrialBalance,
+        config::AccountConfig,
+        domain::{AccountSnapshot, TransactionEvent},
+        error::StoreError,
+        fees::FeeSchedule,
+        invariants,
+        ledger::{Account, Aggregates, InMemoryLedger, Ledger},
+        limits::Limits,
+        rules::RuleSet,
+    },
+    error_handler::{ErrorHandler, LoggingErrorHandler, QuarantineErrorHandler, RejectionReport},
+    journal::Journal,
+    listener::{
+        self,
+        error::{RecordError, SetupError},
+        handler::shutdown_signal,
+        ndjson::ndjson_tcp,
+        open_input, polling,
+        update::UpdateWithCx,
+        InputFormat, UpdateListener,
+    },
+    output::{write_snapshots, OutputFormat, SnapshotOrder, SnapshotWriter},
+    reconcile::{load_snapshots, reconcile, write_differences},
+    stats::{StatsCollector, StatsOutput},
+    validation::dry_run,
+    Pipeline, SnapshotHandler,
+};
+use tokio::io;
+use tokio_util::sync::CancellationToken;
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn Error>> {
-    let path = match env::args().nth(1) {
-        Some(arg) => arg,
-        None => return Err(From::from("expected 1 argument, but got none")),
+/// Generic failure, e.g. the inspected client does not exist.
+const EX_FAILURE: i32 = 1;
+/// The command line was used incorrectly.
+const EX_USAGE: i32 = 64;
+/// The input was found but is not transaction data.
+const EX_DATAERR: i32 = 65;
+/// The input does not exist.
+const EX_NOINPUT: i32 = 66;
+/// A service could not be started, e.g. the listening address is in use.
+const EX_UNAVAILABLE: i32 = 69;
+/// The double-entry books do not balance, or accounts do not match their transactions.
+const EX_SOFTWARE: i32 = 70;
+/// An output file could not be created.
+const EX_CANTCREAT: i32 = 73;
+/// The input could not be read, or the output written, for any other reason.
+const EX_IOERR: i32 = 74;
+/// The input exists but may not be read.
+const EX_NOPERM: i32 = 77;
+/// The run was interrupted a second time while shutting down.
+const EX_INTERRUPTED: i32 = 130;
+
+/// Toy payments engine that applies a series of transactions to client accounts.
+#[derive(Parser)]
+#[clap(version, args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[clap(subcommand)]
+    command: Option<Command>,
+
+    /// Input file, shorthand for `process <INPUT>`.
+    input: Option<PathBuf>,
+
+    #[clap(flatten)]
+    source: SourceArgs,
+
+    #[clap(flatten)]
+    run: RunArgs,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Apply the transactions in INPUT and write the resulting account balances.
+    Process {
+        input: PathBuf,
+
+        #[clap(flatten)]
+        source: SourceArgs,
+
+        /// Record every decoded transaction to this journal, for use with `replay`.
+        #[clap(long)]
+        journal: Option<PathBuf>,
+
+        #[clap(flatten)]
+        run: RunArgs,
+    },
+    /// Dry-run INPUT and report which records would be rejected and why, without writing balances.
+    Validate {
+        input: PathBuf,
+
+        #[clap(flatten)]
+        source: SourceArgs,
+
+        /// Write records that could not be decoded to this CSV file.
+        #[clap(long)]
+        quarantine: Option<PathBuf>,
+
+        #[clap(flatten)]
+        engine: EngineArgs,
+    },
+    /// Rebuild account balances from a journal written by `process --journal`.
+    Replay {
+        journal: PathBuf,
+
+        /// Only replay the first LIMIT journal entries.
+        #[clap(long)]
+        limit: Option<usize>,
+
+        /// Resume from this checkpoint, written by `--checkpoint`, instead of from the
+        /// start of the journal.
+        #[clap(long)]
+        from: Option<PathBuf>,
+
+        #[clap(flatten)]
+        run: RunArgs,
+    },
+    /// Accept transactions with `POST /transactions` on LISTEN until interrupted, then write
+    /// the resulting account balances.
+    #[cfg(feature = "http")]
+    Serve {
+        /// Address to accept transactions on, e.g. `127.0.0.1:8080`.
+        #[clap(long)]
+        listen: std::net::SocketAddr,
+
+        /// Record every received transaction to this journal, for use with `replay`.
+        #[clap(long)]
+        journal: Option<PathBuf>,
+
+        #[clap(flatten)]
+        run: RunArgs,
+    },
+    /// Apply the transactions in INPUT and write the state of a single client.
+    Inspect {
+        input: PathBuf,
+
+        #[clap(flatten)]
+        source: SourceArgs,
+
+        /// The client to write the state of.
+        #[clap(long)]
+        client: u16,
+
+        #[clap(flatten)]
+        run: RunArgs,
+    },
+    /// Compare two account balance CSV files, e.g. ours and a bank's, and list the clients
+    /// whose balances differ.
+    Reconcile {
+        ours: PathBuf,
+
+        theirs: PathBuf,
+
+        /// Write the differences to this file instead of stdout.
+        #[clap(long, short)]
+        output: Option<PathBuf>,
+    },
+}
+
+/// How to read an INPUT, which is a file path, `-` for stdin, or `tcp://ADDR` to accept
+/// NDJSON transactions on ADDR until interrupted.
+#[derive(Args)]
+struct SourceArgs {
+    /// Format of the input: `csv` or `ndjson`. Detected from the file extension or the
+    /// content when omitted.
+    #[clap(long)]
+    input_format: Option<InputFormat>,
+}
+
+#[derive(Args)]
+struct RunArgs {
+    /// Format of the account balances: `csv` or `json`.
+    #[clap(long, default_value = "csv")]
+    format: OutputFormat,
+
+    /// Order of the account balances: `client`, `total` or `locked-first`.
+    #[clap(long, default_value = "client")]
+    order: SnapshotOrder,
+
+    /// Write the account balances to this file instead of stdout.
+    #[clap(long, short)]
+    output: Option<PathBuf>,
+
+    /// Write rejected transactions, and why they were rejected, to this CSV file.
+    #[clap(long)]
+    rejections: Option<PathBuf>,
+
+    /// Write records that could not be decoded to this CSV file.
+    #[clap(long)]
+    quarantine: Option<PathBuf>,
+
+    /// Print statistics of the run to stderr once it finishes.
+    #[clap(long)]
+    stats: bool,
+
+    /// Write statistics of the run to this JSON file once it finishes.
+    #[clap(long)]
+    stats_file: Option<PathBuf>,
+
+    /// Check every account against its recorded transactions once the run finishes.
+    #[clap(long)]
+    verify: bool,
+
+    /// Keep double-entry books and write their trial balance to this CSV file once the run
+    /// finishes.
+    #[clap(long)]
+    trial_balance: Option<PathBuf>,
+
+    /// Write the accounts to this JSON checkpoint once the run finishes, for `replay --from`.
+    #[clap(long)]
+    checkpoint: Option<PathBuf>,
+
+    /// Serve the account balances over HTTP on this address while the run lasts.
+    #[cfg(feature = "http")]
+    #[clap(long)]
+    http: Option<std::net::SocketAddr>,
+
+    #[clap(flatten)]
+    engine: EngineArgs,
+}
+
+impl RunArgs {
+    fn stats(&self) -> Option<StatsCollector> {
+        let mut outputs = Vec::new();
+        if self.stats {
+            outputs.push(StatsOutput::Stderr);
+        }
+        if let Some(path) = &self.stats_file {
+            outputs.push(StatsOutput::Json(path.clone()));
+        }
+        let mut outputs = outputs.into_iter();
+        let collector = StatsCollector::new(outputs.next()?);
+        Some(outputs.fold(collector, StatsCollector::output))
+    }
+}
+
+#[derive(Args)]
+struct EngineArgs {
+    /// Let disputes hold funds even when this makes the available balance negative.
+    #[clap(long)]
+    allow_negative_available: bool,
+
+    /// Accept deposits and withdrawals whose transaction ID is not greater than the previous one.
+    #[clap(long)]
+    allow_out_of_order: bool,
+
+    /// Check every transaction against the risk rules in this JSON file before applying it.
+    #[clap(long)]
+    rules: Option<PathBuf>,
+
+    /// Enforce the per-client withdrawal limits and overdrafts in this CSV file.
+    #[clap(long)]
+    limits: Option<PathBuf>,
+
+    /// Charge the fees in this CSV file alongside deposits, withdrawals and transfers.
+    #[clap(long, requires = "house")]
+    fees: Option<PathBuf>,
+
+    /// The client that collects fees and pays interest.
+    #[clap(long)]
+    house: Option<u16>,
+}
+
+impl EngineArgs {
+    async fn config(&self) -> Result<AccountConfig, Exit> {
+        let rules = match &self.rules {
+            Some(path) => RuleSet::load(path)
+                .await
+                .map_err(|err| Exit::read("rules", path, err))?,
+            None => RuleSet::default(),
+        };
+        let limits = match &self.limits {
+            Some(path) => Limits::load(path)
+                .await
+                .map_err(|err| Exit::read("limits", path, err))?,
+            None => Limits::default(),
+        };
+        let fees = match &self.fees {
+            Some(path) => FeeSchedule::load(path)
+                .await
+                .map_err(|err| Exit::read("fees", path, err))?,
+            None => FeeSchedule::default(),
+        };
+        Ok(AccountConfig {
+            allow_negative_available: self.allow_negative_available,
+            allow_out_of_order: self.allow_out_of_order,
+            rules,
+            limits,
+            fees,
+            house: self.house,
+            double_entry: false,
+        })
+    }
+}
+
+/// Reason for exiting with a non-zero code.
+struct Exit {
+    code: i32,
+    message: String,
+}
+
+impl Exit {
+    fn new(code: i32, message: impl Display) -> Self {
+        Self {
+            code,
+            message: message.to_string(),
+        }
+    }
+
+    fn read(what: &str, path: &Path, error: io::Error) -> Self {
+        let code = match error.kind() {
+            io::ErrorKind::NotFound => EX_NOINPUT,
+            io::ErrorKind::PermissionDenied => EX_NOPERM,
+            io::ErrorKind::InvalidData => EX_DATAERR,
+            _ => EX_IOERR,
+        };
+        Self::new(
+            code,
+            format!("Failed to read {what} `{}`: {error}", path.display()),
+        )
+    }
+
+    fn cant_create(path: &Path, error: impl Display) -> Self {
+        Self::new(
+            EX_CANTCREAT,
+            format!("Failed to create `{}`: {error}", path.display()),
+        )
+    }
+
+    fn unavailable(addr: &std::net::SocketAddr, error: impl Display) -> Self {
+        Self::new(
+            EX_UNAVAILABLE,
+            format!("Failed to serve on `{addr}`: {error}"),
+        )
+    }
+}
+
+impl From<SetupError> for Exit {
+    fn from(error: SetupError) -> Self {
+        let code = match error {
+            SetupError::NotFound { .. } => EX_NOINPUT,
+            SetupError::PermissionDenied { .. } => EX_NOPERM,
+            SetupError::Io { .. } => EX_IOERR,
+            SetupError::InvalidData { .. } => EX_DATAERR,
+        };
+        Self::new(code, error)
+    }
+}
+
+type RecordErrorHandler = Arc<dyn ErrorHandler<RecordError> + Send + Sync>;
+
+/// Hands the errors of the input on to a handler, and remembers whether one of them ended
+/// the input early.
+struct RecordErrors {
+    handler: RecordErrorHandler,
+    fatal: AtomicBool,
+}
+
+impl RecordErrors {
+    /// Fails with `EX_IOERR` if the input could not be read to the end.
+    fn check(&self) -> Result<(), Exit> {
+        match self.fatal.load(Ordering::SeqCst) {
+            true => Err(Exit::new(EX_IOERR, "Failed to read the input to the end")),
+            false => Ok(()),
+        }
+    }
+}
+
+impl ErrorHandler<RecordError> for RecordErrors {
+    fn handle_error(self: Arc<Self>, error: RecordError) -> BoxFuture<'static, ()> {
+        if error.is_fatal() {
+            self.fatal.store(true, Ordering::SeqCst);
+        }
+        Arc::clone(&self.handler).handle_error(error)
+    }
+}
+
+async fn record_error_handler(quarantine: Option<&Path>) -> Result<Arc<RecordErrors>, Exit> {
+    let handler: RecordErrorHandler = match quarantine {
+        Some(path) => QuarantineErrorHandler::create(path)
+            .await
+            .map_err(|err| Exit::cant_create(path, err))?,
+        None => LoggingErrorHandler::with_custom_text("An error from the update listener"),
     };
+    Ok(Arc::new(RecordErrors {
+        handler,
+        fatal: AtomicBool::new(false),
+    }))
+}
+
+async fn config(args: &RunArgs) -> Result<AccountConfig, Exit> {
+    Ok(AccountConfig {
+        double_entry: args.trial_balance.is_some(),
+        ..args.engine.config().await?
+    })
+}
 
-    pipeline(polling(path).await, to_std_out).await;
+async fn ledger(args: &RunArgs) -> Result<Arc<InMemoryLedger<Account>>, Exit> {
+    Ok(InMemoryLedger::with_config(config(args).await?))
+}
+
+async fn accounts(ledger: Arc<InMemoryLedger<Account>>) -> Result<Aggregates<Account>, Exit> {
+    ledger
+        .aggregates()
+        .await
+        .map_err(|err| Exit::new(EX_IOERR, format!("Failed to read the accounts: {err}")))
+}
+
+/// Reports every account in `ledger` whose balance does not match its transactions.
+async fn verify(ledger: Arc<InMemoryLedger<Account>>) -> Result<(), Exit> {
+    let accounts = accounts(ledger).await?;
+    let mismatches = invariants::check(accounts.iter().map(|(id, account)| (*id, account)));
+    for mismatch in &mismatches {
+        eprintln!("{mismatch}");
+    }
+    if !mismatches.is_empty() {
+        return Err(Exit::new(
+            EX_SOFTWARE,
+            format!(
+                "{} of {} accounts do not match their transactions",
+                mismatches.len(),
+                accounts.len()
+            ),
+        ));
+    }
     Ok(())
 }
+
+/// Writes the trial balance of the books of every account in `ledger` to `path`, and
+/// checks the books against the balances of the accounts.
+async fn trial_balance(ledger: Arc<InMemoryLedger<Account>>, path: &Path) -> Result<(), Exit> {
+    let accounts = accounts(ledger).await?;
+    let trial_balance = accounts
+        .iter()
+        .map(|(_, account)| account.book())
+        .collect::<TrialBalance>();
+    let file = tokio::fs::File::create(path)
+        .await
+        .map_err(|err| Exit::cant_create(path, err))?;
+    trial_balance.write(file).await.map_err(|err| {
+        Exit::new(
+            EX_IOERR,
+            format!("Failed to write the trial balance: {err}"),
+        )
+    })?;
+    if !trial_balance.is_balanced() {
+        return Err(Exit::new(
+            EX_SOFTWARE,
+            format!(
+                "The books do not balance: debits {}, credits {}",
+                trial_balance.debits(),
+                trial_balance.credits()
+            ),
+        ));
+    }
+    let mismatches = invariants::check_books(accounts.iter().map(|(id, account)| (*id, account)));
+    for mismatch in &mismatches {
+        eprintln!("{mismatch}");
+    }
+    if !mismatches.is_empty() {
+        return Err(Exit::new(
+            EX_SOFTWARE,
+            format!(
+                "{} of {} accounts do not match the books",
+                mismatches.len(),
+                accounts.len()
+            ),
+        ));
+    }
+    Ok(())
+}
+
+async fn snapshot_writer(args: &RunArgs) -> Result<SnapshotWriter, Exit> {
+    match &args.output {
+        Some(path) => SnapshotWriter::create(path, args.format, args.order)
+            .await
+            .map_err(|err| Exit::cant_create(path, err)),
+        None => Ok(SnapshotWriter::stdout(args.format, args.order)),
+    }
+}
+
+/// Fails with [`EX_IOERR`] if the account balances could not be written.
+async fn written(writer: &SnapshotWriter) -> Result<(), Exit> {
+    writer.check().await.map_err(|err| {
+        Exit::new(
+            EX_IOERR,
+            format!("Failed to write account snapshots: {err}"),
+        )
+    })
+}
+
+/// The ledger a run starts from.
+struct Start {
+    ledger: Arc<InMemoryLedger<Account>>,
+    /// How many journal entries the ledger already includes, e.g. from a checkpoint.
+    resumed: u64,
+}
+
+impl Start {
+    /// Starts from `ledger` at the beginning of the journal.
+    fn new(ledger: Arc<InMemoryLedger<Account>>) -> Self {
+        Self { ledger, resumed: 0 }
+    }
+}
+
+/// Applies every update from `listener` to the ledger of `start`, then hands the snapshots
+/// to `handler`. Stops taking updates once `shutdown` is cancelled.
+async fn apply<L, Item, E, H>(
+    listener: L,
+    error_handler: Arc<dyn ErrorHandler<E> + Send + Sync>,
+    start: Start,
+    handler: H,
+    journal: Option<Arc<Journal>>,
+    args: &RunArgs,
+    shutdown: CancellationToken,
+) -> Result<(), Exit>
+where
+    L: UpdateListener<Item, E> + Send,
+    Item: Into<UpdateWithCx<TransactionEvent>>,
+    E: Debug,
+    H: SnapshotHandler<AccountSnapshot> + Send + Sync + 'static,
+{
+    let Start { ledger, resumed } = start;
+    #[cfg(feature = "http")]
+    if let Some(addr) = &args.http {
+        let server = leviathan::http::QueryServer::bind(addr, Arc::clone(&ledger))
+            .map_err(|err| Exit::unavailable(addr, err))?;
+        eprintln!("Serving account balances on http://{}", server.local_addr());
+        tokio::spawn(server.run());
+    }
+    let mut pipeline = Pipeline::new(Arc::clone(&ledger), handler).error_handler(error_handler);
+    if let Some(path) = &args.rejections {
+        let report = RejectionReport::create(path)
+            .await
+            .map_err(|err| Exit::cant_create(path, err))?;
+        pipeline = pipeline.rejection_handler(report);
+    }
+    if let Some(journal) = journal {
+        pipeline = pipeline.observer(journal);
+    }
+    if let Some(stats) = args.stats() {
+        let existing = Arc::clone(&ledger)
+            .all_snapshots()
+            .await
+            .map_err(|err| Exit::new(EX_IOERR, format!("Failed to read the accounts: {err}")))?;
+        let stats = stats.existing(&existing);
+        pipeline = pipeline.observer(Arc::new(stats));
+    }
+    let checkpointer = Checkpointer::new(resumed);
+    if args.checkpoint.is_some() {
+        pipeline = pipeline.observer(Arc::clone(&checkpointer) as _);
+    }
+    pipeline.run(listener, shutdown).await;
+    if let Some(path) = &args.checkpoint {
+        let checkpoint = checkpointer.checkpoint(accounts(Arc::clone(&ledger)).await?);
+        checkpoint
+            .save(path)
+            .await
+            .map_err(|err| Exit::cant_create(path, err))?;
+    }
+    if args.verify {
+        verify(Arc::clone(&ledger)).await?;
+    }
+    match &args.trial_balance {
+        Some(path) => trial_balance(ledger, path).await,
+        None => Ok(()),
+    }
+}
+
+/// Returns a token that is cancelled on the first SIGINT or SIGTERM, so that the run can
+/// finish the transactions it received, and exits on the second.
+///
+/// Only commands that stop once the token is cancelled install this, the others keep the
+/// default of exiting on the first signal.
+fn shutdown_on_signal() -> CancellationToken {
+    let shutdown = CancellationToken::new();
+    tokio::spawn({
+        let shutdown = shutdown.clone();
+        async move {
+            shutdown_signal().await;
+            eprintln!("Shutting down, finishing the transactions received so far");
+            shutdown.cancel();
+            shutdown_signal().await;
+            process::exit(EX_INTERRUPTED);
+        }
+    });
+    shutdown
+}
+
+/// Completes once `shutdown` is cancelled.
+fn cancelled(shutdown: &CancellationToken) -> impl Future<Output = ()> + Send + 'static {
+    let shutdown = shutdown.clone();
+    async move { shutdown.cancelled().await }
+}
+
+async fn journal(path: Option<PathBuf>) -> Result<Option<Arc<Journal>>, Exit> {
+    match path {
+        Some(path) => Journal::create(&path)
+            .await
+            .map(Some)
+            .map_err(|err| Exit::cant_create(&path, err)),
+        None => Ok(None),
+    }
+}
+
+/// Opens `input` as described by [`SourceArgs`].
+async fn open(
+    input: &Path,
+    source: &SourceArgs,
+    shutdown: &CancellationToken,
+) -> Result<impl UpdateListener<TransactionEvent, RecordError>, Exit> {
+    let addr = match input
+        .to_str()
+        .and_then(|input| input.strip_prefix("tcp://"))
+    {
+        Some(addr) => addr,
+        None => {
+            let listener = open_input(input.to_path_buf(), source.input_format).await?;
+            return Ok(listener::either(Either::Left(listener)));
+        }
+    };
+    if source.input_format == Some(InputFormat::Csv) {
+        return Err(Exit::new(EX_USAGE, "`tcp://` inputs only accept ndjson"));
+    }
+    let addr = addr
+        .parse()
+        .map_err(|err| Exit::new(EX_USAGE, format!("Invalid address `{addr}`: {err}")))?;
+    let (local_addr, listener) = ndjson_tcp(&addr, cancelled(shutdown))
+        .await
+        .map_err(|err| Exit::unavailable(&addr, err))?;
+    eprintln!("Accepting NDJSON transactions on tcp://{local_addr}");
+    Ok(listener::either(Either::Right(listener)))
+}
+
+async fn process(
+    input: PathBuf,
+    source: SourceArgs,
+    journal: Option<PathBuf>,
+    args: RunArgs,
+    shutdown: CancellationToken,
+) -> Result<(), Exit> {
+    let listener = open(&input, &source, &shutdown).await?;
+    let errors = record_error_handler(args.quarantine.as_deref()).await?;
+    let journal = self::journal(journal).await?;
+    let writer = snapshot_writer(&args).await?;
+    apply(
+        listener,
+        Arc::clone(&errors) as _,
+        Start::new(ledger(&args).await?),
+        writer.clone(),
+        journal,
+        &args,
+        shutdown,
+    )
+    .await?;
+    written(&writer).await?;
+    errors.check()
+}
+
+#[cfg(feature = "http")]
+async fn serve(
+    listen: std::net::SocketAddr,
+    journal: Option<PathBuf>,
+    args: RunArgs,
+    shutdown: CancellationToken,
+) -> Result<(), Exit> {
+    let (addr, listener) = leviathan::listener::http::http_listener(&listen, cancelled(&shutdown))
+        .map_err(|err| Exit::unavailable(&listen, err))?;
+    eprintln!("Accepting transactions on http://{addr}/transactions");
+    let journal = self::journal(journal).await?;
+    let writer = snapshot_writer(&args).await?;
+    let error_handler = LoggingErrorHandler::new();
+    apply(
+        listener,
+        error_handler,
+        Start::new(ledger(&args).await?),
+        writer.clone(),
+        journal,
+        &args,
+        shutdown,
+    )
+    .await?;
+    written(&writer).await
+}
+
+async fn validate(
+    input: PathBuf,
+    source: SourceArgs,
+    quarantine: Option<PathBuf>,
+    engine: EngineArgs,
+) -> Result<(), Exit> {
+    // A dry run has nothing to write on shutdown, so it does not stop a TCP input.
+    let listener = open(&input, &source, &CancellationToken::new()).await?;
+    let errors = record_error_handler(quarantine.as_deref()).await?;
+
+    let report = dry_run(listener, Arc::clone(&errors), engine.config().await?).await;
+    print!("{report}");
+    errors.check()?;
+    if report.errors.count > 0 {
+        return Err(Exit::new(
+            EX_SOFTWARE,
+            format!(
+                "{} of {} records could not be checked",
+                report.errors.count, report.records
+            ),
+        ));
+    }
+    let failed = report.invalid + report.rejected();
+    if failed > 0 {
+        return Err(Exit::new(
+            EX_DATAERR,
+            format!("{failed} of {} records would fail", report.records),
+        ));
+    }
+    Ok(())
+}
+
+async fn replay(
+    journal: PathBuf,
+    limit: Option<usize>,
+    from: Option<PathBuf>,
+    args: RunArgs,
+    shutdown: CancellationToken,
+) -> Result<(), Exit> {
+    let start = match &from {
+        Some(path) => {
+            let checkpoint = Checkpoint::load(path)
+                .await
+                .map_err(|err| Exit::read("checkpoint", path, err))?;
+            Start {
+                ledger: InMemoryLedger::with_aggregates(checkpoint.accounts, config(&args).await?),
+                resumed: checkpoint.transactions,
+            }
+        }
+        None => Start::new(ledger(&args).await?),
+    };
+    let skipped = usize::try_from(start.resumed).unwrap_or(usize::MAX);
+    if let Some(limit) = limit.filter(|limit| *limit < skipped) {
+        return Err(Exit::new(
+            EX_USAGE,
+            format!("`--limit {limit}` is before the checkpoint, which includes {skipped} entries"),
+        ));
+    }
+    let listener = listener::skip(polling(journal).await?, skipped);
+    let errors = record_error_handler(args.quarantine.as_deref()).await?;
+    let error_handler = Arc::clone(&errors) as _;
+    let writer = snapshot_writer(&args).await?;
+    match limit {
+        Some(limit) => {
+            let listener = listener::take(listener, limit - skipped);
+            apply(
+                listener,
+                error_handler,
+                start,
+                writer.clone(),
+                None,
+                &args,
+                shutdown,
+            )
+            .await?;
+        }
+        None => {
+            apply(
+                listener,
+                error_handler,
+                start,
+                writer.clone(),
+                None,
+                &args,
+                shutdown,
+            )
+            .await?;
+        }
+    }
+    written(&writer).await?;
+    errors.check()
+}
+
+async fn inspect(
+    input: PathBuf,
+    source: SourceArgs,
+    client: u16,
+    args: RunArgs,
+    shutdown: CancellationToken,
+) -> Result<(), Exit> {
+    let listener = open(&input, &source, &shutdown).await?;
+    let errors = record_error_handler(args.quarantine.as_deref()).await?;
+    let ledger = ledger(&args).await?;
+    let handler = |_| async {};
+    apply(
+        listener,
+        Arc::clone(&errors) as _,
+        Start::new(Arc::clone(&ledger)),
+        handler,
+        None,
+        &args,
+        shutdown,
+    )
+    .await?;
+    errors.check()?;
+
+    let snapshot = ledger.snapshot(client).await.map_err(|err| match err {
+        StoreError::NotFound(_) => {
+            Exit::new(EX_FAILURE, format!("Client `{client}` does not exist"))
+        }
+        err => Exit::new(EX_IOERR, format!("Failed to read client state: {err}")),
+    })?;
+    let result = match &args.output {
+        Some(path) => {
+            let file = tokio::fs::File::create(path)
+                .await
+                .map_err(|err| Exit::cant_create(path, err))?;
+            write_snapshots(file, args.format, &[snapshot]).await
+        }
+        None => write_snapshots(io::stdout(), args.format, &[snapshot]).await,
+    };
+    result.map_err(|err| Exit::new(EX_IOERR, format!("Failed to write client state: {err}")))
+}
+
+async fn reconcile_files(
+    ours: PathBuf,
+    theirs: PathBuf,
+    output: Option<PathBuf>,
+) -> Result<(), Exit> {
+    let read = |path: PathBuf| async move {
+        load_snapshots(&path)
+            .await
+            .map_err(|err| Exit::read("balances", &path, err))
+    };
+    let differences = reconcile(&read(ours).await?, &read(theirs).await?);
+    let result = match &output {
+        Some(path) => {
+            let file = tokio::fs::File::create(path)
+                .await
+                .map_err(|err| Exit::cant_create(path, err))?;
+            write_differences(file, &differences).await
+        }
+        None => write_differences(io::stdout(), &differences).await,
+    };
+    result.map_err(|err| Exit::new(EX_IOERR, format!("Failed to write the differences: {err}")))?;
+    if !differences.is_empty() {
+        return Err(Exit::new(
+            EX_FAILURE,
+            format!("Found {} differences", differences.len()),
+        ));
+    }
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() {
+    let cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        Err(err) => {
+            let _ = err.print();
+            process::exit(if err.use_stderr() { EX_USAGE } else { 0 });
+        }
+    };
+
+    let result = match (cli.command, cli.input) {
+        (
+            Some(Command::Process {
+                input,
+                source,
+                journal,
+                run,
+            }),
+            _,
+        ) => process(input, source, journal, run, shutdown_on_signal()).await,
+        (
+            Some(Command::Validate {
+                input,
+                source,
+                quarantine,
+                engine,
+            }),
+            _,
+        ) => validate(input, source, quarantine, engine).await,
+        (
+            Some(Command::Replay {
+                journal,
+                limit,
+                from,
+                run,
+            }),
+            _,
+        ) => replay(journal, limit, from, run, shutdown_on_signal()).await,
+        #[cfg(feature = "http")]
+        (
+            Some(Command::Serve {
+                listen,
+                journal,
+                run,
+            }),
+            _,
+        ) => serve(listen, journal, run, shutdown_on_signal()).await,
+        (
+            Some(Command::Inspect {
+                input,
+                source,
+                client,
+                run,
+            }),
+            _,
+        ) => inspect(input, source, client, run, shutdown_on_signal()).await,
+        (
+            Some(Command::Reconcile {
+                ours,
+                theirs,
+                output,
+            }),
+            _,
+        ) => reconcile_files(ours, theirs, output).await,
+        (None, Some(input)) => {
+            process(input, cli.source, None, cli.run, shutdown_on_signal()).await
+        }
+        (None, None) => Err(Exit::new(
+            EX_USAGE,
+            "expected an input file or a subcommand, see `--help`",
+        )),
+    };
+
+    if let Err(exit) = result {
+        eprintln!("{}", exit.message);
+        process::exit(exit.code);
+    }
+}
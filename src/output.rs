@@ -0,0 +1,218 @@
+use std::{path::Path, str::FromStr, sync::Arc};
+
+use futures::future::BoxFuture;
+use tokio::{
+    fs::File,
+    io::{self, AsyncWrite, AsyncWriteExt},
+    sync::Mutex,
+};
+
+use crate::{engine::domain::AccountSnapshot, SnapshotHandler};
+
+/// Format in which account snapshots are written.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    /// One CSV row per account, with a header.
+    Csv,
+    /// A JSON array with one object per account.
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(Self::Csv),
+            "json" => Ok(Self::Json),
+            other => Err(format!(
+                "unknown output format `{other}`, expected `csv` or `json`"
+            )),
+        }
+    }
+}
+
+/// Order in which account snapshots are written.
+///
+/// Every order is total, so identical snapshots are always written identically.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SnapshotOrder {
+    /// Ascending client ID.
+    Client,
+    /// Descending total funds, then ascending client ID.
+    Total,
+    /// Locked accounts before unlocked ones, then ascending client ID.
+    LockedFirst,
+}
+
+impl SnapshotOrder {
+    pub fn sort(self, snapshots: &mut [AccountSnapshot]) {
+        match self {
+            Self::Client => snapshots.sort_by_key(|data| data.client_id),
+            Self::Total => snapshots.sort_by(|a, b| {
+                b.total
+                    .cmp(&a.total)
+                    .then_with(|| a.client_id.cmp(&b.client_id))
+            }),
+            Self::LockedFirst => snapshots.sort_by_key(|data| (!data.locked, data.client_id)),
+        }
+    }
+}
+
+impl FromStr for SnapshotOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "client" => Ok(Self::Client),
+            "total" => Ok(Self::Total),
+            "locked-first" => Ok(Self::LockedFirst),
+            other => Err(format!(
+                "unknown order `{other}`, expected `client`, `total` or `locked-first`"
+            )),
+        }
+    }
+}
+
+/// Writes `snapshots` to `writer` in the given `format`.
+pub async fn write_snapshots<W>(
+    mut writer: W,
+    format: OutputFormat,
+    snapshots: &[AccountSnapshot],
+) -> io::Result<()>
+where
+    W: AsyncWrite + Unpin + Send,
+{
+    match format {
+        OutputFormat::Csv => {
+            let mut wri = csv_async::AsyncWriterBuilder::new()
+                .has_headers(true)
+                .create_serializer(&mut writer);
+            for data in snapshots {
+                wri.serialize(data).await.map_err(io::Error::from)?;
+            }
+            wri.flush().await?;
+        }
+        OutputFormat::Json => {
+            let mut json = serde_json::to_vec_pretty(snapshots)?;
+            json.push(b'\n');
+            writer.write_all(&json).await?;
+        }
+    }
+    writer.flush().await
+}
+
+/// A [`SnapshotHandler`] that writes the snapshots to stdout or a file.
+///
+/// Clones share the output, so a clone can be handed to a dispatcher and the original
+/// [`check`](Self::check)ed once it is done.
+#[derive(Clone)]
+pub struct SnapshotWriter {
+    format: OutputFormat,
+    order: SnapshotOrder,
+    writer: Arc<Mutex<Box<dyn AsyncWrite + Send + Unpin>>>,
+    /// The error of the first write that failed.
+    failed: Arc<Mutex<Option<io::Error>>>,
+}
+
+impl SnapshotWriter {
+    pub fn stdout(format: OutputFormat, order: SnapshotOrder) -> Self {
+        Self::new(format, order, Box::new(io::stdout()))
+    }
+
+    /// Creates (or truncates) the file at `path` the snapshots will be written to.
+    pub async fn create<P>(path: P, format: OutputFormat, order: SnapshotOrder) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let file = File::create(path).await?;
+        Ok(Self::new(format, order, Box::new(file)))
+    }
+
+    fn new(
+        format: OutputFormat,
+        order: SnapshotOrder,
+        writer: Box<dyn AsyncWrite + Send + Unpin>,
+    ) -> Self {
+        Self {
+            format,
+            order,
+            writer: Arc::new(Mutex::new(writer)),
+            failed: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Fails with the error of the first write that failed, if any.
+    pub async fn check(&self) -> io::Result<()> {
+        match self.failed.lock().await.take() {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+}
+
+impl SnapshotHandler<AccountSnapshot> for SnapshotWriter {
+    fn handle(self: Arc<Self>, snapshot: Vec<AccountSnapshot>) -> BoxFuture<'static, ()>
+    where
+        AccountSnapshot: Send + 'static,
+    {
+        Box::pin(async move {
+            let mut snapshot = snapshot;
+            self.order.sort(&mut snapshot);
+            let mut writer = self.writer.lock().await;
+            if let Err(err) = write_snapshots(&mut *writer, self.format, &snapshot).await {
+                self.failed.lock().await.get_or_insert(err);
+            }
+        })
+    }
+}
+
+/// A [`SnapshotHandler`] that keeps the snapshots in memory, e.g. to inspect them in tests.
+///
+/// Clones share the snapshots, so a clone can be handed to a dispatcher and the original
+/// read once it is done.
+pub struct MemorySink<S> {
+    snapshots: Arc<Mutex<Vec<S>>>,
+}
+
+impl<S> MemorySink<S> {
+    pub fn new() -> Self {
+        Self {
+            snapshots: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Every snapshot handed to the sink so far, in the order they were handed to it.
+    pub async fn snapshots(&self) -> Vec<S>
+    where
+        S: Clone,
+    {
+        self.snapshots.lock().await.clone()
+    }
+}
+
+impl<S> Default for MemorySink<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S> Clone for MemorySink<S> {
+    fn clone(&self) -> Self {
+        Self {
+            snapshots: Arc::clone(&self.snapshots),
+        }
+    }
+}
+
+impl<S> SnapshotHandler<S> for MemorySink<S>
+where
+    S: Send + 'static,
+{
+    fn handle(self: Arc<Self>, snapshot: Vec<S>) -> BoxFuture<'static, ()>
+    where
+        S: Send + 'static,
+    {
+        Box::pin(async move { self.snapshots.lock().await.extend(snapshot) })
+    }
+}
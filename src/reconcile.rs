@@ -0,0 +1,182 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+};
+
+use serde::Serialize;
+use tokio::io::{self, AsyncRead, AsyncWrite, AsyncWriteExt};
+
+use crate::engine::domain::AccountSnapshot;
+
+/// One field in which two account snapshots of the same client differ.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Difference {
+    pub client: u16,
+    /// `available`, `held`, `total` or `locked`, or `account` if only one side has the
+    /// client.
+    pub field: &'static str,
+    pub ours: String,
+    pub theirs: String,
+}
+
+/// Reads account snapshots in the CSV format the engine writes them in.
+///
+/// Fails with [`io::ErrorKind::InvalidData`] if a client appears more than once.
+pub async fn read_snapshots<R>(reader: R) -> io::Result<Vec<AccountSnapshot>>
+where
+    R: AsyncRead + Unpin + Send,
+{
+    let mut reader = csv_async::AsyncReaderBuilder::new()
+        .trim(csv_async::Trim::All)
+        .create_deserializer(reader);
+    let invalid = |err| io::Error::new(io::ErrorKind::InvalidData, err);
+    let headers = reader.headers().await.map_err(invalid)?.clone();
+    let mut record = csv_async::StringRecord::new();
+    let mut lines = HashMap::new();
+    let mut snapshots = Vec::new();
+    while reader.read_record(&mut record).await.map_err(invalid)? {
+        let snapshot = record
+            .deserialize::<AccountSnapshot>(Some(&headers))
+            .map_err(invalid)?;
+        let line = record.position().map_or(0, |position| position.line());
+        if let Some(first) = lines.insert(snapshot.client_id, line) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Line {line}: client `{}` already appears on line {first}",
+                    snapshot.client_id
+                ),
+            ));
+        }
+        snapshots.push(snapshot);
+    }
+    Ok(snapshots)
+}
+
+/// Reads the account snapshots in the CSV file at `path`, see [`read_snapshots`].
+pub async fn load_snapshots(path: &Path) -> io::Result<Vec<AccountSnapshot>> {
+    read_snapshots(tokio::fs::File::open(path).await?).await
+}
+
+/// Compares `ours` with `theirs` client by client, and returns every difference by
+/// ascending client ID. Amounts are compared by value, so `1.50` equals `1.5`.
+pub fn reconcile(ours: &[AccountSnapshot], theirs: &[AccountSnapshot]) -> Vec<Difference> {
+    let mut clients = BTreeMap::<u16, (Option<&AccountSnapshot>, Option<&AccountSnapshot>)>::new();
+    for snapshot in ours {
+        clients.entry(snapshot.client_id).or_default().0 = Some(snapshot);
+    }
+    for snapshot in theirs {
+        clients.entry(snapshot.client_id).or_default().1 = Some(snapshot);
+    }
+
+    let mut differences = Vec::new();
+    for (client, sides) in clients {
+        let (ours, theirs) = match sides {
+            (Some(ours), Some(theirs)) => (ours, theirs),
+            (ours, theirs) => {
+                let side = |snapshot: Option<&AccountSnapshot>| match snapshot {
+                    Some(_) => "present".to_owned(),
+                    None => "missing".to_owned(),
+                };
+                differences.push(Difference {
+                    client,
+                    field: "account",
+                    ours: side(ours),
+                    theirs: side(theirs),
+                });
+                continue;
+            }
+        };
+        let fields = [
+            ("available", ours.available, theirs.available),
+            ("held", ours.held, theirs.held),
+            ("total", ours.total, theirs.total),
+        ];
+        for (field, ours, theirs) in fields {
+            if ours != theirs {
+                differences.push(Difference {
+                    client,
+                    field,
+                    ours: ours.to_string(),
+                    theirs: theirs.to_string(),
+                });
+            }
+        }
+        if ours.locked != theirs.locked {
+            differences.push(Difference {
+                client,
+                field: "locked",
+                ours: ours.locked.to_string(),
+                theirs: theirs.locked.to_string(),
+            });
+        }
+    }
+    differences
+}
+
+/// Writes `differences` to `writer` as CSV, with a header.
+pub async fn write_differences<W>(mut writer: W, differences: &[Difference]) -> io::Result<()>
+where
+    W: AsyncWrite + Unpin + Send,
+{
+    let mut wri = csv_async::AsyncWriterBuilder::new()
+        .has_headers(true)
+        .create_serializer(&mut writer);
+    for difference in differences {
+        wri.serialize(difference).await.map_err(io::Error::from)?;
+    }
+    wri.flush().await?;
+    drop(wri);
+    writer.flush().await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_reconcile() {
+        let ours = read_snapshots(
+            "client,available,held,total,locked\n1,1.50,0,1.5,false\n2,3,1,4,false\n3,0,0,0,true\n"
+                .as_bytes(),
+        )
+        .await
+        .unwrap();
+        let theirs = read_snapshots(
+            "client,available,held,total,locked\n2,4,0,4,true\n1,1.5,0,1.5,false\n4,1,0,1,false\n"
+                .as_bytes(),
+        )
+        .await
+        .unwrap();
+
+        let differences = reconcile(&ours, &theirs);
+        let mut csv = Vec::new();
+        write_differences(&mut csv, &differences).await.unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            concat!(
+                "client,field,ours,theirs\n",
+                "2,available,3,4\n",
+                "2,held,1,0\n",
+                "2,locked,false,true\n",
+                "3,account,present,missing\n",
+                "4,account,missing,present\n",
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_client() {
+        let error = read_snapshots(
+            "client,available,held,total,locked\n1,1,0,1,false\n2,0,0,0,false\n1,2,0,2,false\n"
+                .as_bytes(),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(
+            error.to_string(),
+            "Line 4: client `1` already appears on line 2"
+        );
+    }
+}
@@ -0,0 +1,297 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt,
+    path::PathBuf,
+    time::Instant,
+};
+
+use futures::future::BoxFuture;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use tokio::sync::Mutex;
+
+use crate::{
+    engine::{
+        domain::{AccountSnapshot, TransactionEvent, TransactionType},
+        ledger::Account,
+    },
+    TransactionObserver,
+};
+
+/// Where the statistics of a run are written once it finishes.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StatsOutput {
+    /// A human readable summary on stderr.
+    Stderr,
+    /// A JSON document at the given path.
+    Json(PathBuf),
+}
+
+/// Accepted and rejected transactions of one [`TransactionType`].
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct TypeStats {
+    pub accepted: u64,
+    pub rejected: u64,
+    /// Sum of the amounts of the accepted transactions.
+    pub volume: Decimal,
+}
+
+/// Statistics of a single run.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct RunStats {
+    pub transactions: BTreeMap<TransactionType, TypeStats>,
+    pub accepted: u64,
+    pub rejected: u64,
+    /// Sum of the amounts of every accepted transaction, see [`TypeStats::volume`] for
+    /// the volume of each type.
+    pub volume: Decimal,
+    /// Accepted transactions flagged by each rule.
+    pub flagged: BTreeMap<String, u64>,
+    /// Accounts of clients whose first transaction was observed in this run, not counting
+    /// the house account.
+    pub accounts_created: usize,
+    /// Accounts the run locked, not counting those that were locked before it.
+    pub accounts_locked: usize,
+    pub elapsed_secs: f64,
+}
+
+impl fmt::Display for RunStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "transactions: {} accepted, {} rejected",
+            self.accepted, self.rejected
+        )?;
+        for (transaction_type, stats) in &self.transactions {
+            writeln!(
+                f,
+                "  {transaction_type:?}: {} accepted, {} rejected, volume {}",
+                stats.accepted, stats.rejected, stats.volume
+            )?;
+        }
+        writeln!(f, "volume: {}", self.volume)?;
+        for (rule, count) in &self.flagged {
+            writeln!(f, "flagged by `{rule}`: {count}")?;
+        }
+        writeln!(
+            f,
+            "accounts: {} created, {} locked",
+            self.accounts_created, self.accounts_locked
+        )?;
+        writeln!(f, "elapsed: {:.3}s", self.elapsed_secs)
+    }
+}
+
+/// Collects [`RunStats`] while transactions are applied.
+pub struct StatsCollector {
+    started: Instant,
+    outputs: Vec<StatsOutput>,
+    stats: Mutex<RunStats>,
+    /// Clients with an account from before the run.
+    existing: HashSet<u16>,
+    /// Clients whose account was locked before the run.
+    locked: HashSet<u16>,
+    /// Clients of the observed transactions.
+    clients: Mutex<HashSet<u16>>,
+}
+
+impl StatsCollector {
+    /// Creates a collector; the elapsed time is measured from this call.
+    pub fn new(output: StatsOutput) -> Self {
+        Self {
+            started: Instant::now(),
+            outputs: vec![output],
+            stats: Mutex::new(RunStats::default()),
+            existing: HashSet::new(),
+            locked: HashSet::new(),
+            clients: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Also writes the statistics to `output`.
+    pub fn output(mut self, output: StatsOutput) -> Self {
+        self.outputs.push(output);
+        self
+    }
+
+    /// Does not count `accounts` as created, nor as locked if they already are, as they
+    /// exist before the run.
+    pub fn existing<'a>(mut self, accounts: impl IntoIterator<Item = &'a AccountSnapshot>) -> Self {
+        for account in accounts {
+            self.existing.insert(account.client_id);
+            if account.locked {
+                self.locked.insert(account.client_id);
+            }
+        }
+        self
+    }
+
+    /// Counts `event`, which the ledger either applied or rejected.
+    pub async fn record(&self, event: &TransactionEvent, accepted: bool) {
+        if !self.existing.contains(&event.client_id) {
+            self.clients.lock().await.insert(event.client_id);
+        }
+        let mut stats = self.stats.lock().await;
+        let counts = stats
+            .transactions
+            .entry(event.transaction_type.clone())
+            .or_default();
+        if accepted {
+            let amount = event.amount.unwrap_or_default();
+            counts.accepted += 1;
+            counts.volume += amount;
+            stats.accepted += 1;
+            stats.volume += amount;
+        } else {
+            counts.rejected += 1;
+            stats.rejected += 1;
+        }
+    }
+
+    /// Counts a transaction the rule `rule` flagged.
+    pub async fn record_flag(&self, rule: &str) {
+        *self
+            .stats
+            .lock()
+            .await
+            .flagged
+            .entry(rule.to_owned())
+            .or_default() += 1;
+    }
+
+    /// Completes the statistics with the final account `snapshots` and writes them out.
+    pub async fn finish(&self, snapshots: &[AccountSnapshot]) -> RunStats {
+        let mut stats = self.stats.lock().await.clone();
+        let clients = self.clients.lock().await;
+        stats.accounts_created = snapshots
+            .iter()
+            .filter(|data| clients.contains(&data.client_id))
+            .count();
+        stats.accounts_locked = snapshots
+            .iter()
+            .filter(|data| data.locked && !self.locked.contains(&data.client_id))
+            .count();
+        stats.elapsed_secs = self.started.elapsed().as_secs_f64();
+
+        for output in &self.outputs {
+            match output {
+                StatsOutput::Stderr => eprint!("{stats}"),
+                StatsOutput::Json(path) => {
+                    let written = match serde_json::to_vec_pretty(&stats) {
+                        Ok(json) => tokio::fs::write(path, json).await,
+                        Err(err) => Err(err.into()),
+                    };
+                    if let Err(err) = written {
+                        eprintln!("Failed to write statistics to `{}`: {err}", path.display());
+                    }
+                }
+            }
+        }
+        stats
+    }
+}
+
+impl TransactionObserver<Account> for StatsCollector {
+    fn observe<'a>(&'a self, event: &'a TransactionEvent, accepted: bool) -> BoxFuture<'a, ()> {
+        Box::pin(self.record(event, accepted))
+    }
+
+    fn flag<'a>(&'a self, _event: &'a TransactionEvent, rule: &'a str) -> BoxFuture<'a, ()> {
+        Box::pin(self.record_flag(rule))
+    }
+
+    fn finish<'a>(&'a self, snapshots: &'a [AccountSnapshot]) -> BoxFuture<'a, ()> {
+        Box::pin(async move {
+            self.finish(snapshots).await;
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rust_decimal_macros::dec;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_collect_stats() {
+        let event = |transaction_type, tx_id, amount| TransactionEvent {
+            client_id: 1,
+            tx_id,
+            transaction_type,
+            amount,
+            counterparty: None,
+        };
+        let snapshot = |client_id, locked| AccountSnapshot {
+            client_id,
+            available: dec!(-4),
+            held: dec!(10),
+            total: dec!(6),
+            locked,
+        };
+        let collector = StatsCollector::new(StatsOutput::Stderr).existing(&[snapshot(2, true)]);
+        collector
+            .record(&event(TransactionType::Deposit, 1, Some(dec!(10))), true)
+            .await;
+        collector
+            .record(&event(TransactionType::Withdrawal, 2, Some(dec!(4))), true)
+            .await;
+        collector
+            .record(
+                &event(TransactionType::Withdrawal, 3, Some(dec!(40))),
+                false,
+            )
+            .await;
+        collector
+            .record(&event(TransactionType::Dispute, 1, None), true)
+            .await;
+        collector
+            .record(
+                &TransactionEvent {
+                    counterparty: Some(2),
+                    ..event(TransactionType::Transfer, 4, Some(dec!(3)))
+                },
+                true,
+            )
+            .await;
+        collector
+            .record(&event(TransactionType::Fee, 5, Some(dec!(1))), true)
+            .await;
+        collector
+            .record(
+                &TransactionEvent {
+                    client_id: 2,
+                    ..event(TransactionType::Dispute, 7, None)
+                },
+                false,
+            )
+            .await;
+        collector.record_flag("large").await;
+
+        // Client 2 existed and was locked before the run and 9 is the house, neither was
+        // created nor locked by it.
+        let stats = collector
+            .finish(&[snapshot(1, true), snapshot(2, true), snapshot(9, false)])
+            .await;
+
+        assert_eq!(stats.accepted, 5);
+        assert_eq!(stats.rejected, 2);
+        assert_eq!(stats.volume, dec!(18));
+        assert_eq!(
+            stats.transactions[&TransactionType::Transfer].volume,
+            dec!(3)
+        );
+        assert_eq!(stats.transactions[&TransactionType::Fee].volume, dec!(1));
+        assert_eq!(stats.flagged["large"], 1);
+        assert_eq!(stats.accounts_created, 1);
+        assert_eq!(stats.accounts_locked, 1);
+        assert_eq!(
+            stats.transactions[&TransactionType::Withdrawal],
+            TypeStats {
+                accepted: 1,
+                rejected: 1,
+                volume: dec!(4)
+            }
+        );
+    }
+}
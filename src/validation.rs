@@ -0,0 +1,213 @@
+use std::{collections::BTreeMap, fmt, sync::Arc};
+
+use futures::StreamExt;
+
+use crate::{
+    engine::{
+        config::AccountConfig,
+        domain::TransactionEvent,
+        error::StoreError,
+        ledger::{Account, InMemoryLedger, Ledger},
+    },
+    error_handler::ErrorHandler,
+    listener::UpdateListener,
+};
+
+/// How many example transaction IDs are kept per kind of rejection.
+const MAX_EXAMPLES: usize = 5;
+
+/// Rejections of one kind of [`LedgerError`](crate::engine::error::LedgerError), or the
+/// transactions one rule flagged.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RejectionGroup {
+    pub count: usize,
+    /// The first few transaction IDs of the group.
+    pub examples: Vec<u32>,
+}
+
+impl RejectionGroup {
+    fn add(&mut self, tx_id: u32) {
+        self.count += 1;
+        if self.examples.len() < MAX_EXAMPLES {
+            self.examples.push(tx_id);
+        }
+    }
+}
+
+/// Outcome of a dry run over an input.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ValidationReport {
+    /// Records read from the input.
+    pub records: usize,
+    /// Records that could not be decoded.
+    pub invalid: usize,
+    /// Decoded transactions the engine would reject, grouped by error kind.
+    pub rejections: BTreeMap<&'static str, RejectionGroup>,
+    /// Transactions the engine would accept but rules flag, grouped by rule.
+    pub flags: BTreeMap<String, RejectionGroup>,
+    /// Decoded transactions the ledger failed to apply for another reason than a rejection.
+    pub errors: RejectionGroup,
+}
+
+impl ValidationReport {
+    pub fn rejected(&self) -> usize {
+        self.rejections.values().map(|group| group.count).sum()
+    }
+
+    pub fn accepted(&self) -> usize {
+        self.records - self.invalid - self.rejected() - self.errors.count
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "records:  {}", self.records)?;
+        writeln!(f, "invalid:  {}", self.invalid)?;
+        writeln!(f, "accepted: {}", self.accepted())?;
+        writeln!(f, "rejected: {}", self.rejected())?;
+        for (kind, group) in &self.rejections {
+            writeln!(
+                f,
+                "  {kind}: {} (tx {})",
+                group.count,
+                examples(&group.examples)
+            )?;
+        }
+        if self.errors.count > 0 {
+            writeln!(
+                f,
+                "errors:   {} (tx {})",
+                self.errors.count,
+                examples(&self.errors.examples)
+            )?;
+        }
+        for (rule, group) in &self.flags {
+            writeln!(
+                f,
+                "flagged by `{rule}`: {} (tx {})",
+                group.count,
+                examples(&group.examples)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn examples(tx_ids: &[u32]) -> String {
+    tx_ids
+        .iter()
+        .map(u32::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Applies every update from `listener` to a throwaway ledger and reports what would
+/// be rejected, without producing any balances.
+///
+/// Records that cannot be decoded are handed to `error_handler`. Transactions the ledger
+/// fails to apply for another reason than a rejection are counted in
+/// [`ValidationReport::errors`].
+pub async fn dry_run<L, E, Eh>(
+    mut listener: L,
+    error_handler: Arc<Eh>,
+    config: AccountConfig,
+) -> ValidationReport
+where
+    L: UpdateListener<TransactionEvent, E>,
+    Eh: ErrorHandler<E> + ?Sized,
+{
+    let ledger = InMemoryLedger::<Account>::with_config(config);
+    let mut report = ValidationReport::default();
+    {
+        let stream = listener.as_stream();
+        tokio::pin!(stream);
+        while let Some(update) = stream.next().await {
+            report.records += 1;
+            match update {
+                Ok(event) => {
+                    let tx_id = event.tx_id;
+                    let result = Arc::clone(&ledger)
+                        .process_transaction(event.client_id, tx_id, event)
+                        .await;
+                    match result {
+                        Ok(applied) => {
+                            for rule in applied.flags {
+                                report.flags.entry(rule).or_default().add(tx_id);
+                            }
+                        }
+                        Err(StoreError::Rejected(error)) => report
+                            .rejections
+                            .entry(error.kind())
+                            .or_default()
+                            .add(tx_id),
+                        Err(error) => {
+                            eprintln!("Failed to apply transaction {tx_id}: {error}");
+                            report.errors.add(tx_id);
+                        }
+                    }
+                }
+                Err(error) => {
+                    report.invalid += 1;
+                    Arc::clone(&error_handler).handle_error(error).await;
+                }
+            }
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::listener::csv_reader;
+
+    #[tokio::test]
+    async fn test_dry_run() {
+        let data = r#"type,client,tx,amount
+deposit,1,1,10
+withdrawal,1,2,50
+withdrawal,1,3,60
+deposit,2,4,oops
+dispute,1,9,
+deposit,1,5,1
+"#;
+        let report = dry_run(
+            csv_reader("inline.csv", data.as_bytes()),
+            Arc::new(|_| async {}),
+            AccountConfig {
+                rules: serde_json::from_str(
+                    r#"{"rules": [{"name": "large", "kind": "large_amount", "type": "deposit", "threshold": "5", "action": "flag"}]}"#,
+                )
+                .unwrap(),
+                ..AccountConfig::default()
+            },
+        )
+        .await;
+
+        assert_eq!(report.records, 6);
+        assert_eq!(report.invalid, 1);
+        assert_eq!(report.accepted(), 2);
+        assert_eq!(report.errors, RejectionGroup::default());
+        assert_eq!(
+            report.rejections.get("InsufficientFunds"),
+            Some(&RejectionGroup {
+                count: 2,
+                examples: vec![2, 3]
+            })
+        );
+        assert_eq!(
+            report.rejections.get("TransactionNotFound"),
+            Some(&RejectionGroup {
+                count: 1,
+                examples: vec![9]
+            })
+        );
+        assert_eq!(
+            report.flags.get("large"),
+            Some(&RejectionGroup {
+                count: 1,
+                examples: vec![1]
+            })
+        );
+    }
+}
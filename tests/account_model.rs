@@ -0,0 +1,211 @@
+use std::collections::{HashMap, HashSet};
+
+use leviathan::engine::{
+    config::AccountConfig,
+    domain::{AccountSnapshot, TransactionEvent, TransactionType},
+    ledger::{Account, Aggregate},
+};
+use proptest::prelude::*;
+use rust_decimal::Decimal;
+
+const CLIENT: u16 = 1;
+
+/// A straightforward reading of the spec for a single client, to check `Account` against.
+#[derive(Debug, Default)]
+struct Model {
+    available: Decimal,
+    held: Decimal,
+    locked: bool,
+    /// The latest deposit or withdrawal that was applied; any ID may come first.
+    previous_tx_id: Option<u32>,
+    /// Amounts of the deposits and withdrawals that were applied.
+    amounts: HashMap<u32, Decimal>,
+    disputed: HashSet<u32>,
+    allow_negative_available: bool,
+}
+
+impl Model {
+    /// The state of an account before its first transaction.
+    fn new(allow_negative_available: bool) -> Self {
+        Self {
+            allow_negative_available,
+            ..Self::default()
+        }
+    }
+
+    fn follows(&self, tx_id: u32) -> bool {
+        match self.previous_tx_id {
+            Some(previous) => tx_id > previous,
+            None => true,
+        }
+    }
+
+    /// Applies `event` and returns whether it was accepted.
+    fn apply(&mut self, event: &TransactionEvent) -> bool {
+        if self.locked {
+            return false;
+        }
+        let tx_id = event.tx_id;
+        match (&event.transaction_type, event.amount) {
+            (TransactionType::Deposit, Some(amount)) if self.follows(tx_id) => {
+                self.available += amount;
+                self.amounts.insert(tx_id, amount);
+                self.previous_tx_id = Some(tx_id);
+            }
+            (TransactionType::Withdrawal, Some(amount))
+                if self.follows(tx_id) && self.available >= amount =>
+            {
+                self.available -= amount;
+                self.amounts.insert(tx_id, amount);
+                self.previous_tx_id = Some(tx_id);
+            }
+            (TransactionType::Dispute, _) => {
+                let amount = match self.amounts.get(&tx_id) {
+                    Some(amount) if !self.disputed.contains(&tx_id) => *amount,
+                    _ => return false,
+                };
+                if !self.allow_negative_available && self.available < amount {
+                    return false;
+                }
+                self.available -= amount;
+                self.held += amount;
+                self.disputed.insert(tx_id);
+            }
+            (TransactionType::Resolve, _) if self.disputed.remove(&tx_id) => {
+                let amount = self.amounts[&tx_id];
+                self.held -= amount;
+                self.available += amount;
+            }
+            (TransactionType::Chargeback, _) if self.disputed.remove(&tx_id) => {
+                self.held -= self.amounts[&tx_id];
+                self.locked = true;
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    fn snapshot(&self) -> AccountSnapshot {
+        AccountSnapshot {
+            client_id: CLIENT,
+            available: self.available,
+            held: self.held,
+            total: self.available + self.held,
+            locked: self.locked,
+        }
+    }
+}
+
+/// A step of a generated transaction sequence. Transaction IDs are assigned when the
+/// sequence is built, so that disputes mostly refer to earlier transactions.
+#[derive(Debug, Clone)]
+enum Op {
+    Deposit(i64),
+    Withdrawal(i64),
+    /// A deposit that reuses an earlier transaction ID.
+    StaleDeposit(i64, prop::sample::Index),
+    Dispute(prop::sample::Index),
+    Resolve(prop::sample::Index),
+    Chargeback(prop::sample::Index),
+    /// A dispute of a transaction that never happened.
+    UnknownDispute,
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    // Amounts in cents, so that sums stay exact but are not all whole numbers.
+    let cents = 0..100_000i64;
+    prop_oneof![
+        4 => cents.clone().prop_map(Op::Deposit),
+        3 => cents.clone().prop_map(Op::Withdrawal),
+        1 => (cents, any::<prop::sample::Index>()).prop_map(|(c, i)| Op::StaleDeposit(c, i)),
+        2 => any::<prop::sample::Index>().prop_map(Op::Dispute),
+        1 => any::<prop::sample::Index>().prop_map(Op::Resolve),
+        1 => any::<prop::sample::Index>().prop_map(Op::Chargeback),
+        1 => Just(Op::UnknownDispute),
+    ]
+}
+
+fn events(ops: Vec<Op>) -> Vec<TransactionEvent> {
+    let event = |transaction_type, tx_id, amount| TransactionEvent {
+        client_id: CLIENT,
+        tx_id,
+        transaction_type,
+        amount,
+        counterparty: None,
+    };
+    let amount = |cents| Some(Decimal::new(cents, 2));
+    let mut tx_ids = Vec::new();
+    let mut next = 1;
+    let mut events = Vec::with_capacity(ops.len());
+    for op in ops {
+        let earlier = |index: prop::sample::Index| {
+            if tx_ids.is_empty() {
+                0
+            } else {
+                *index.get(&tx_ids)
+            }
+        };
+        events.push(match op {
+            Op::Deposit(cents) | Op::Withdrawal(cents) => {
+                let transaction_type = match op {
+                    Op::Deposit(_) => TransactionType::Deposit,
+                    _ => TransactionType::Withdrawal,
+                };
+                tx_ids.push(next);
+                next += 1;
+                event(transaction_type, next - 1, amount(cents))
+            }
+            Op::StaleDeposit(cents, index) => {
+                event(TransactionType::Deposit, earlier(index), amount(cents))
+            }
+            Op::Dispute(index) => event(TransactionType::Dispute, earlier(index), None),
+            Op::Resolve(index) => event(TransactionType::Resolve, earlier(index), None),
+            Op::Chargeback(index) => event(TransactionType::Chargeback, earlier(index), None),
+            Op::UnknownDispute => event(TransactionType::Dispute, u32::MAX, None),
+        });
+    }
+    events
+}
+
+proptest! {
+    // Thousands of sequences unless `PROPTEST_CASES` asks for another number.
+    #![proptest_config(ProptestConfig::with_cases(
+        std::env::var("PROPTEST_CASES")
+            .ok()
+            .and_then(|cases| cases.parse().ok())
+            .unwrap_or(2_000)
+    ))]
+
+    #[test]
+    fn account_matches_model(
+        ops in prop::collection::vec(op(), 1..60),
+        allow_negative_available in any::<bool>(),
+    ) {
+        let config = AccountConfig {
+            allow_negative_available,
+            ..AccountConfig::default()
+        };
+        let events = events(ops);
+        // The first transaction creates the account whether or not it applies.
+        let (mut account, created) = Account::create(events[0].tx_id, events[0].clone(), &config);
+        let mut model = Model::new(allow_negative_available);
+        prop_assert_eq!(created.is_ok(), model.apply(&events[0]), "{:?}", &events[0]);
+        prop_assert_eq!(account.snapshot(CLIENT), model.snapshot());
+
+        for event in &events[1..] {
+            let before = account.snapshot(CLIENT);
+            let accepted = account.apply_tx(event.tx_id, event.clone(), &config).is_ok();
+            let after = account.snapshot(CLIENT);
+
+            prop_assert_eq!(accepted, model.apply(event), "{:?}", event);
+            prop_assert_eq!(&after, &model.snapshot());
+            prop_assert_eq!(after.total, after.available + after.held);
+            prop_assert!(after.held >= Decimal::default());
+            prop_assert_eq!(account.recompute(), account.balance().clone());
+            if before.locked {
+                prop_assert!(!accepted);
+                prop_assert_eq!(&after, &before);
+            }
+        }
+    }
+}
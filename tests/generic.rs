@@ -0,0 +1,261 @@
+use std::{collections::BTreeMap, convert::Infallible, sync::Arc};
+
+use futures::{future::BoxFuture, stream};
+use leviathan::{
+    engine::{
+        error::StoreError,
+        ledger::{Aggregate, Applied, Ledger, LedgerResult, Route},
+    },
+    error_handler::LoggingErrorHandler,
+    listener::handler::Dispatcher,
+    listener::StatefulListener,
+    TransactionDispatcher,
+};
+use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+
+/// Points earned (or spent, when negative) by a loyalty programme member.
+#[derive(Debug, Clone)]
+struct PointsEvent {
+    member: u32,
+    seq: u64,
+    points: i64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct PointsSnapshot {
+    member: u32,
+    balance: i64,
+}
+
+#[derive(Debug, Clone)]
+struct PointsAccount {
+    balance: i64,
+}
+
+impl Aggregate for PointsAccount {
+    type Error = String;
+    type ID = u32;
+    type TxID = u64;
+    type EventData = PointsEvent;
+    type Snapshot = PointsSnapshot;
+
+    fn new(_id: Self::TxID, tx_data: Self::EventData) -> Self {
+        Self {
+            balance: tx_data.points.max(0),
+        }
+    }
+
+    fn apply_tx(
+        &mut self,
+        _tx_id: Self::TxID,
+        tx_data: Self::EventData,
+    ) -> Result<(), Self::Error> {
+        if self.balance + tx_data.points < 0 {
+            return Err("not enough points".to_string());
+        }
+        self.balance += tx_data.points;
+        Ok(())
+    }
+
+    fn snapshot(&self, member: Self::ID) -> Self::Snapshot {
+        PointsSnapshot {
+            member,
+            balance: self.balance,
+        }
+    }
+}
+
+impl Route<u32, u64> for PointsEvent {
+    fn route(&self) -> (u32, u64) {
+        (self.member, self.seq)
+    }
+}
+
+#[tokio::test]
+async fn test_custom_aggregate_pipeline() {
+    let events = vec![
+        PointsEvent {
+            member: 7,
+            seq: 1,
+            points: 100,
+        },
+        PointsEvent {
+            member: 3,
+            seq: 2,
+            points: 40,
+        },
+        PointsEvent {
+            member: 7,
+            seq: 3,
+            points: -30,
+        },
+        PointsEvent {
+            member: 3,
+            seq: 4,
+            points: -50,
+        },
+    ];
+    let listener = StatefulListener::new(
+        Some(events.into_iter().map(Ok::<_, Infallible>)),
+        |events: &mut Option<_>| stream::iter(events.take().into_iter().flatten()),
+    );
+
+    let result = Arc::new(Mutex::new(Vec::new()));
+    let sink = Arc::clone(&result);
+    let dispatcher =
+        TransactionDispatcher::<PointsAccount, _, _>::new(move |snapshot: Vec<PointsSnapshot>| {
+            let sink = Arc::clone(&sink);
+            async move { sink.lock().await.extend(snapshot) }
+        });
+    Dispatcher::new()
+        .messages_handler(dispatcher)
+        .dispatch_with_listener(
+            listener,
+            LoggingErrorHandler::new(),
+            CancellationToken::new(),
+        )
+        .await;
+
+    assert_eq!(
+        *result.lock().await,
+        vec![
+            PointsSnapshot {
+                member: 3,
+                balance: 40,
+            },
+            PointsSnapshot {
+                member: 7,
+                balance: 70,
+            },
+        ]
+    );
+}
+
+/// A ledger that only implements the required methods.
+#[derive(Default)]
+struct Members(Mutex<BTreeMap<u32, PointsAccount>>);
+
+impl Ledger<PointsAccount> for Members {
+    fn process_transaction(
+        self: Arc<Self>,
+        id: u32,
+        tx_id: u64,
+        transaction: PointsEvent,
+    ) -> BoxFuture<'static, LedgerResult<PointsAccount, Applied<u32>>> {
+        Box::pin(async move {
+            let mut members = self.0.lock().await;
+            match members.get_mut(&id) {
+                Some(account) => account
+                    .apply_tx(tx_id, transaction)
+                    .map_err(StoreError::Rejected)?,
+                None => {
+                    members.insert(id, PointsAccount::new(tx_id, transaction));
+                }
+            }
+            Ok(Applied {
+                id,
+                flags: Vec::new(),
+            })
+        })
+    }
+
+    fn snapshot(
+        self: Arc<Self>,
+        id: u32,
+    ) -> BoxFuture<'static, LedgerResult<PointsAccount, PointsSnapshot>> {
+        Box::pin(async move {
+            let members = self.0.lock().await;
+            let account = members.get(&id).ok_or(StoreError::NotFound(id))?;
+            Ok(account.snapshot(id))
+        })
+    }
+
+    fn all_snapshots(
+        self: Arc<Self>,
+    ) -> BoxFuture<'static, LedgerResult<PointsAccount, Vec<PointsSnapshot>>> {
+        Box::pin(async move {
+            let members = self.0.lock().await;
+            Ok(members
+                .iter()
+                .map(|(id, account)| account.snapshot(*id))
+                .collect())
+        })
+    }
+}
+
+#[tokio::test]
+async fn test_custom_ledger_defaults() {
+    let ledger = Arc::new(Members::default());
+    let event = PointsEvent {
+        member: 7,
+        seq: 1,
+        points: 10,
+    };
+    Arc::clone(&ledger)
+        .process_transaction(7, 1, event)
+        .await
+        .unwrap();
+
+    assert!(matches!(
+        Arc::clone(&ledger).transactions(7).await,
+        Err(StoreError::Unsupported(_))
+    ));
+    assert!(matches!(
+        Arc::clone(&ledger).aggregates().await,
+        Err(StoreError::Unsupported(_))
+    ));
+    assert_eq!(
+        ledger.snapshot(7).await.unwrap(),
+        PointsSnapshot {
+            member: 7,
+            balance: 10,
+        }
+    );
+}
+
+/// A ledger whose snapshots cannot be read.
+struct Unreadable;
+
+impl Ledger<PointsAccount> for Unreadable {
+    fn process_transaction(
+        self: Arc<Self>,
+        id: u32,
+        _tx_id: u64,
+        _transaction: PointsEvent,
+    ) -> BoxFuture<'static, LedgerResult<PointsAccount, Applied<u32>>> {
+        Box::pin(async move {
+            Ok(Applied {
+                id,
+                flags: Vec::new(),
+            })
+        })
+    }
+
+    fn snapshot(
+        self: Arc<Self>,
+        id: u32,
+    ) -> BoxFuture<'static, LedgerResult<PointsAccount, PointsSnapshot>> {
+        Box::pin(async move { Err(StoreError::NotFound(id)) })
+    }
+
+    fn all_snapshots(
+        self: Arc<Self>,
+    ) -> BoxFuture<'static, LedgerResult<PointsAccount, Vec<PointsSnapshot>>> {
+        Box::pin(async { Err(StoreError::Unsupported("snapshots")) })
+    }
+}
+
+#[tokio::test]
+async fn test_completion_without_snapshots() {
+    let dispatcher = TransactionDispatcher::<PointsAccount, _, _>::with_ledger(
+        Arc::new(Unreadable),
+        |_: Vec<PointsSnapshot>| async { panic!("no snapshots to hand off") },
+    );
+    let completion = dispatcher.completion();
+    let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+    drop(tx);
+
+    assert!(dispatcher.run(rx).await.is_none());
+    assert!(!completion.wait().await);
+}
@@ -0,0 +1,156 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use futures::future::BoxFuture;
+use leviathan::{
+    engine::{
+        domain::{AccountSnapshot, TransactionEvent},
+        ledger::{Account, InMemoryLedger, Ledger},
+    },
+    listener::{csv_reader, error::RecordError, ndjson::ndjson_reader},
+    output::{write_snapshots, OutputFormat, SnapshotOrder},
+    pipeline, Pipeline, TransactionObserver,
+};
+use rust_decimal_macros::dec;
+use tokio::{
+    io::AsyncWriteExt,
+    sync::{Mutex, Notify},
+};
+use tokio_util::sync::CancellationToken;
+
+fn transactions() -> String {
+    let mut data = String::from("type,client,tx,amount\n");
+    for tx in 1..=500u32 {
+        let client = (tx * 7919) % 211;
+        data.push_str(&format!("deposit,{client},{tx},{}.{}\n", tx % 13, tx % 7));
+        if tx % 5 == 0 {
+            data.push_str(&format!("dispute,{client},{tx},\n"));
+        }
+        if tx % 15 == 0 {
+            data.push_str(&format!("chargeback,{client},{tx},\n"));
+        }
+    }
+    data
+}
+
+async fn run(data: String, order: SnapshotOrder) -> Vec<u8> {
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let sink = Arc::clone(&output);
+    pipeline(
+        csv_reader("inline.csv", std::io::Cursor::new(data)),
+        move |mut snapshot: Vec<AccountSnapshot>| {
+            let sink = Arc::clone(&sink);
+            async move {
+                order.sort(&mut snapshot);
+                let mut sink = sink.lock().await;
+                write_snapshots(&mut *sink, OutputFormat::Csv, &snapshot)
+                    .await
+                    .unwrap();
+            }
+        },
+    )
+    .await;
+    let output = output.lock().await.clone();
+    output
+}
+
+#[tokio::test]
+async fn test_output_is_byte_identical_across_runs() {
+    for order in [
+        SnapshotOrder::Client,
+        SnapshotOrder::Total,
+        SnapshotOrder::LockedFirst,
+    ] {
+        let first = run(transactions(), order).await;
+        let second = run(transactions(), order).await;
+        assert!(!first.is_empty());
+        assert_eq!(first, second, "{order:?} output differs between runs");
+    }
+}
+
+#[tokio::test]
+async fn test_locked_first_order() {
+    let output = run(transactions(), SnapshotOrder::LockedFirst).await;
+    let locked = String::from_utf8(output)
+        .unwrap()
+        .lines()
+        .skip(1)
+        .map(|line| line.ends_with("true"))
+        .collect::<Vec<_>>();
+    let boundary = locked.iter().position(|locked| !locked).unwrap();
+    assert!(boundary > 0);
+    assert!(locked[boundary..].iter().all(|locked| !locked));
+}
+
+#[tokio::test]
+async fn test_pipeline_with_existing_ledger() {
+    let data = "type,client,tx,amount\ndeposit,1,1,2.5\nrefund,1,2,1\ndeposit,1,3,1.5\n";
+    let ledger = InMemoryLedger::<Account>::new();
+    let invalid = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&invalid);
+
+    Pipeline::new(Arc::clone(&ledger), |_: Vec<AccountSnapshot>| async {})
+        .error_handler(Arc::new(move |_: RecordError| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            async {}
+        }))
+        .run(
+            csv_reader("inline.csv", std::io::Cursor::new(data)),
+            CancellationToken::new(),
+        )
+        .await;
+
+    assert_eq!(invalid.load(Ordering::SeqCst), 1);
+    assert_eq!(ledger.snapshot(1).await.unwrap().total, dec!(4));
+}
+
+/// Notifies once a transaction was observed.
+struct Observed(Notify);
+
+impl TransactionObserver<Account> for Observed {
+    fn observe<'a>(&'a self, _event: &'a TransactionEvent, _accepted: bool) -> BoxFuture<'a, ()> {
+        self.0.notify_one();
+        Box::pin(async {})
+    }
+
+    fn finish<'a>(&'a self, _snapshots: &'a [AccountSnapshot]) -> BoxFuture<'a, ()> {
+        Box::pin(async {})
+    }
+}
+
+#[tokio::test]
+async fn test_pipeline_shutdown() {
+    let (mut source, reader) = tokio::io::duplex(1024);
+    source
+        .write_all(b"{\"type\": \"deposit\", \"client\": 1, \"tx\": 1, \"amount\": \"4\"}\n")
+        .await
+        .unwrap();
+    let ledger = InMemoryLedger::<Account>::new();
+    let shutdown = CancellationToken::new();
+    let observed = Arc::new(Observed(Notify::new()));
+    let emitted = Arc::new(Mutex::new(Vec::new()));
+    let sink = Arc::clone(&emitted);
+    let run = tokio::spawn(
+        Pipeline::new(
+            Arc::clone(&ledger),
+            move |snapshot: Vec<AccountSnapshot>| {
+                let sink = Arc::clone(&sink);
+                async move { sink.lock().await.extend(snapshot) }
+            },
+        )
+        .observer(Arc::clone(&observed) as _)
+        .run(ndjson_reader("live", reader), shutdown.clone()),
+    );
+
+    // The source stays open, so the run only ends once it is shut down.
+    observed.0.notified().await;
+    shutdown.cancel();
+    run.await.unwrap();
+
+    let emitted = emitted.lock().await;
+    assert_eq!(emitted.len(), 1);
+    assert_eq!(emitted[0].total, dec!(4));
+    drop(source);
+}